@@ -0,0 +1,97 @@
+/**
+ * Broad phase throughput: the spatial grid against the all pairs loop it replaced.
+ * Bodies are spread at a constant density, so bigger runs cover a bigger field. The field
+ * wraps like the game frame does.
+ *
+ * cargo bench --bench spatial_grid
+ */
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use asteroids_bevy::spatial::{minimum_image, SpatialGrid, GRID_CELL_SIZE};
+
+// about ten asteroids on a 1280x720 field
+const AREA_PER_BODY: f32 = 90_000.0;
+const RADII: std::ops::Range<f32> = 5.0..80.0;
+const MIN_BENCH_TIME: Duration = Duration::from_millis(500);
+
+fn field(n: usize) -> Vec2 {
+    Vec2::splat((n as f32 * AREA_PER_BODY).sqrt())
+}
+
+fn bodies(n: usize) -> Vec<(Entity, Vec2, f32)> {
+    let mut rng = SmallRng::seed_from_u64(n as u64);
+    let side = field(n).x;
+    (0..n)
+        .map(|i| {
+            (
+                Entity::from_raw(i as u32),
+                Vec2::new(rng.gen_range(0.0..side), rng.gen_range(0.0..side)),
+                rng.gen_range(RADII),
+            )
+        })
+        .collect()
+}
+
+fn all_pairs(bodies: &[(Entity, Vec2, f32)], field: Vec2) -> usize {
+    let mut pairs = 0;
+    for (i, (_, ap, ar)) in bodies.iter().enumerate() {
+        for (_, bp, br) in bodies[i + 1..].iter() {
+            if minimum_image(*ap, *bp, field).length_squared() < (ar + br).powi(2) {
+                pairs += 1;
+            }
+        }
+    }
+
+    pairs
+}
+
+fn grid_pairs(grid: &mut SpatialGrid, bodies: &[(Entity, Vec2, f32)]) -> usize {
+    grid.clear();
+    for (entity, pos, radius) in bodies {
+        grid.insert(*entity, *pos, *radius);
+    }
+    grid.build_pairs();
+    grid.pairs().len()
+}
+
+/**
+ * Runs f until MIN_BENCH_TIME has passed, returns the mean time per run and its result
+ */
+fn bench(mut f: impl FnMut() -> usize) -> (Duration, usize) {
+    let start = Instant::now();
+    let mut runs = 0;
+    let mut result = 0;
+    while runs == 0 || start.elapsed() < MIN_BENCH_TIME {
+        result = f();
+        runs += 1;
+    }
+
+    (start.elapsed() / runs, result)
+}
+
+fn main() {
+    println!(
+        "{:>8} {:>14} {:>14} {:>16} {:>8}",
+        "bodies", "all pairs", "grid", "grid bodies/s", "pairs"
+    );
+    for n in [100, 1_000, 10_000] {
+        let bodies = bodies(n);
+        let mut grid = SpatialGrid::new(GRID_CELL_SIZE, Vec2::ZERO, field(n));
+
+        let (naive, naive_pairs) = bench(|| all_pairs(&bodies, field(n)));
+        let (gridded, grid_pairs) = bench(|| grid_pairs(&mut grid, &bodies));
+        assert_eq!(naive_pairs, grid_pairs, "the grid missed or doubled pairs");
+
+        println!(
+            "{:>8} {:>14?} {:>14?} {:>16.0} {:>8}",
+            n,
+            naive,
+            gridded,
+            n as f64 / gridded.as_secs_f64(),
+            grid_pairs
+        );
+    }
+}
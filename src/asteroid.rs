@@ -1,8 +1,18 @@
-use crate::{GAME_BORDER_OFFSET, GAME_FRAME_HEIGHT, GAME_FRAME_WIDTH};
+use crate::{
+    boundary::{wrapped_delta, wrapped_distance},
+    config::{AsteroidConfig, GameConfig},
+    geometry::{fracture, polygon_centroid, polygon_radius},
+    movement::Mass,
+    state::{despawn_system, AppState},
+    timestep::SimulationApp,
+    wave::{reset_wave_system, wave_system, CurrentStage, StageBanner, WaveDirector, WavePhase},
+    GameSystem, GAME_BORDER_OFFSET, GAME_FRAME_HEIGHT, GAME_FRAME_WIDTH,
+};
 
 use super::{
-    random::Random, AngularVelocity, BoundaryWrap, Bounding, Debug, ShapeBundle, SpeedLimit,
-    Velocity, DARK, LIGHT, POLY_LINE_WIDTH,
+    random::{AsteroidGeneration, AsteroidSpawning, AsteroidSplitting, Random},
+    AngularVelocity, BoundaryWrap, Bounding, Debug, ShapeBundle, SpeedLimit, Velocity, DARK, LIGHT,
+    POLY_LINE_WIDTH,
 };
 use bevy::{
     math::{vec2, vec3},
@@ -13,15 +23,110 @@ use bevy_prototype_lyon::{
     shapes,
 };
 use rand::Rng;
-use std::ops::RangeInclusive;
+use std::ops::{Range, RangeInclusive};
+
+/**
+ * Spawning asteroids in waves and splitting them when they are destroyed
+ */
+pub struct AsteroidPlugin;
+
+impl Plugin for AsteroidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentStage>()
+            .init_resource::<WaveDirector>()
+            .add_event::<AsteroidSpawnEvent>()
+            .add_event::<AsteroidSplitEvent>()
+            .add_simulation_system(asteroid_spawn_system)
+            .add_simulation_system(
+                asteroid_generation_system
+                    .after(GameSystem::Collision)
+                    .after(asteroid_spawn_system),
+            )
+            .add_simulation_system(asteroid_split_system.after(GameSystem::Collision))
+            .add_simulation_system(
+                wave_system
+                    .after(asteroid_generation_system)
+                    .after(asteroid_split_system),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Playing).with_system(reset_wave_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing)
+                    .with_system(despawn_system::<Asteroid>)
+                    .with_system(despawn_system::<StageBanner>),
+            );
+    }
+}
+
 pub const ASTEROID_SIZES: (
     RangeInclusive<f32>,
     RangeInclusive<f32>,
     RangeInclusive<f32>,
 ) = (60.0..=80.0, 30.0..=50.00, 10.0..=20.0);
 
+// fragments are pushed apart by this speed, and spawned this far apart
+const SEPARATION_IMPULSE: Range<f32> = 20.0..40.0;
+const SEPARATION_GAP: f32 = 2.0;
+
 #[derive(Debug, Component)]
 pub struct Asteroid;
+
+/**
+ * Size class of an asteroid. Decides its health, what it is worth and what it breaks into.
+ */
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq)]
+pub enum AsteroidSize {
+    Large,
+    Medium,
+    Small,
+}
+
+impl AsteroidSize {
+    pub fn health(&self) -> Health {
+        match self {
+            AsteroidSize::Large => Health(30.0),
+            AsteroidSize::Medium => Health(20.0),
+            AsteroidSize::Small => Health(1.0),
+        }
+    }
+
+    pub fn score(&self) -> u32 {
+        match self {
+            AsteroidSize::Large => 20,
+            AsteroidSize::Medium => 50,
+            AsteroidSize::Small => 100,
+        }
+    }
+
+    /**
+     * The size of the fragments left behind when destroyed, small asteroids crumble to dust
+     */
+    pub fn fragments(&self) -> Option<AsteroidSize> {
+        match self {
+            AsteroidSize::Large => Some(AsteroidSize::Medium),
+            AsteroidSize::Medium => Some(AsteroidSize::Small),
+            AsteroidSize::Small => None,
+        }
+    }
+
+    /**
+     * How deep the dents in the outline go, radii have to be bigger than this
+     */
+    pub fn roughness(&self) -> f32 {
+        match self {
+            AsteroidSize::Large => 30.0,
+            AsteroidSize::Medium => 15.0,
+            AsteroidSize::Small => 3.0,
+        }
+    }
+
+    pub fn radius(&self, config: &AsteroidConfig) -> RangeInclusive<f32> {
+        match self {
+            AsteroidSize::Large => config.large.clone(),
+            AsteroidSize::Medium => config.medium.clone(),
+            AsteroidSize::Small => config.small.clone(),
+        }
+    }
+}
 #[derive(Debug, Component)]
 pub struct Health(pub f32);
 #[derive(Debug, Component)]
@@ -30,14 +135,20 @@ pub struct Damage(pub f32);
 pub struct AsteroidSpawnEvent {
     pub pos: Vec2,
     pub radius: f32,
+    pub size: AsteroidSize,
     pub amount: i32,
 }
 
 pub struct AsteroidSplitEvent {
+    // outline of the destroyed asteroid, in its local space
     pub parent_points: Vec<Vec2>,
-    pub pos: Vec2,
-    pub radius: f32,
-    pub amount: f32,
+    pub parent_transform: Transform,
+    pub parent_velocity: Vec2,
+    // where it was hit and which way the hit was going, in world space
+    pub impact: Vec2,
+    pub direction: Vec2,
+    pub size: AsteroidSize,
+    pub amount: usize,
 }
 
 #[derive(Debug, Component, Clone, Deref)]
@@ -53,26 +164,28 @@ pub struct AsteroidBundle {
     pub ang_vel: AngularVelocity,
     pub marker: Asteroid,
     pub points: Points,
+    pub mass: Mass,
+    pub size: AsteroidSize,
     #[bundle]
     pub shape: ShapeBundle,
 }
 
-pub fn velocity(center: &Vec2, radius: &f32, rng: &mut Random) -> Velocity {
-    let v = match *radius as usize {
-        60..=80 => {
+pub fn velocity(center: &Vec2, size: &AsteroidSize, rng: &mut impl Rng) -> Velocity {
+    let v = match size {
+        AsteroidSize::Large => {
             let dest = vec2(1.0, 1.0);
             let angle = center.angle_between(dest);
             let direction = Quat::from_rotation_z(angle) * -Vec3::Y; //TODO: find out why this works
             let force = rng.gen_range(10.0..50.00);
             vec2(force * direction.x, force * direction.y)
         }
-        30..=50 => {
+        AsteroidSize::Medium => {
             let direction =
                 Quat::from_rotation_z((rng.gen_range(0..360) as f32).to_radians()) * -Vec3::Y; //TODO: find out why this works
             let force = rng.gen_range(20.0..60.00);
             vec2(force * direction.x, force * direction.y)
         }
-        _ => {
+        AsteroidSize::Small => {
             let direction =
                 Quat::from_rotation_z((rng.gen_range(0..360) as f32).to_radians()) * -Vec3::Y; //TODO: find out why this works
             let force = rng.gen_range(30.0..70.00);
@@ -83,35 +196,34 @@ pub fn velocity(center: &Vec2, radius: &f32, rng: &mut Random) -> Velocity {
     Velocity::from(v)
 }
 
-pub fn health(radius: &f32) -> Health {
-    let h = match *radius as usize {
-        60..=80 => 30.0,
-        30..=50 => 20.0,
-        _ => 1.0,
-    };
-
-    Health(h)
-}
-
 pub fn asteroid_spawn_system(
-    mut rng: Local<Random>,
+    time: Res<Time>,
+    mut director: ResMut<WaveDirector>,
+    mut rng: Local<Random<AsteroidSpawning>>,
     mut ev_asteroid_spawn: EventWriter<AsteroidSpawnEvent>,
     asteroids: Query<(&Transform, &Bounding, With<Asteroid>)>,
+    config: Res<GameConfig>,
 ) {
-    if !rng.gen_bool(1.0 / 6.0) {
+    if director.phase != WavePhase::Spawning
+        || director.remaining == 0
+        || !director.timer.tick(time.delta()).just_finished()
+    {
         return;
     }
 
     let h = (GAME_FRAME_HEIGHT - GAME_BORDER_OFFSET) / 2.0;
     let w = (GAME_FRAME_WIDTH - GAME_BORDER_OFFSET) / 2.0;
 
-    let size = rng.gen_range(0..=10);
-    let radius = match size {
-        0..=3 => rng.gen_range(ASTEROID_SIZES.0),
-        4..=6 => rng.gen_range(ASTEROID_SIZES.1),
-        7..=9 => rng.gen_range(ASTEROID_SIZES.2),
-        _ => rng.gen_range(ASTEROID_SIZES.0),
+    let [large, medium, small] = director.config.size_mix;
+    let size = rng.gen_range(0..(large + medium + small).max(1));
+    let size = if size < large {
+        AsteroidSize::Large
+    } else if size < large + medium {
+        AsteroidSize::Medium
+    } else {
+        AsteroidSize::Small
     };
+    let radius = rng.gen_range(size.radius(&config.asteroids));
 
     let side = rng.gen_range(0..=3);
     let pos = match side {
@@ -121,125 +233,97 @@ pub fn asteroid_spawn_system(
         _ => vec3(rng.gen_range(-w..w), h, 1.0),
     };
 
-    let Vec3 { x: x1, y: y1, z: _ } = pos;
     let r1 = radius;
     for (transform, bounding, _) in asteroids.iter() {
-        let Vec3 { x: x2, y: y2, z: _ } = transform.translation;
         let r2 = bounding.0;
-        let d = ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt();
+        let d = wrapped_distance(pos.truncate(), transform.translation.truncate());
         if d < r1 + r2 {
             // spawn collides with existing asteroid
             return;
         }
     }
 
+    director.remaining -= 1;
     let amount = 1;
     // må fikse til å kun bruke vec3..
     ev_asteroid_spawn.send(AsteroidSpawnEvent {
         amount,
         pos: vec2(pos.x, pos.y),
         radius,
+        size,
     });
 }
 
+/**
+ * Breaks destroyed asteroids along their outline. The first crack follows the line of impact,
+ * the fragments keep the parent's momentum and are pushed apart.
+ */
 pub fn asteroid_split_system(
     mut commands: Commands,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<AsteroidSplitting>>,
     mut ev_asteroid_split: EventReader<AsteroidSplitEvent>,
+    config: Res<GameConfig>,
     debug: Res<Debug>,
 ) {
     for AsteroidSplitEvent {
-        parent_points: pp,
-        pos,
-        radius,
+        parent_points,
+        parent_transform,
+        parent_velocity,
+        impact,
+        direction,
+        size,
         amount,
     } in ev_asteroid_split.iter()
     {
-        let len = pp.len() as f32;
-        let num = (len / amount).floor() as usize;
-        let a = &pp[..=num];
-        let b = &pp[num..=(num * 2)];
-        let c = &pp[(num * 2)..];
-        let center_point = vec2(
-            pp.iter().map(|p| p.x).sum::<f32>() / len,
-            pp.iter().map(|p| p.y).sum::<f32>() / len,
-        );
-
-        //[&c[0], a.to_vec(), &b[0]].concat()
-
-        // let z = c[0].clone();
-        let mut a_points = vec![center_point.clone(), c[c.len() - 1]];
-        a_points.extend_from_slice(a);
-        a_points.push(b[0]);
-        let a_shape = shapes::Polygon {
-            points: a_points.clone(),
-            closed: true,
-        };
-
-        let mut b_points = vec![center_point.clone(), a[a.len() - 1]];
-        b_points.extend_from_slice(b);
-        b_points.push(c[0]);
-        let b_shape = shapes::Polygon {
-            points: b_points.clone(),
-            closed: true,
-        };
-
-        let mut c_points = vec![center_point.clone(), b[b.len() - 1]];
-        c_points.extend_from_slice(c);
-        c_points.push(a[0]);
-        let c_shape = shapes::Polygon {
-            points: c_points.clone(),
-            closed: true,
-        };
-
-        let shapes = [
-            (a_shape, a_points),
-            (b_shape, b_points),
-            (c_shape, c_points),
-        ];
-
-        for (shape, points) in shapes.iter() {
-            let centroid = vec2(
-                points.iter().map(|p| p.x).sum::<f32>() / len,
-                points.iter().map(|p| p.y).sum::<f32>() / len,
-            );
-
-            let bounding = points
-                .iter()
-                .map(|p| ((p.x).powi(2) + (p.y).powi(2)).sqrt())
-                .sum::<f32>()
-                / points.len() as f32;
+        // the crack in the parent's local space
+        let pos = parent_transform.translation.truncate();
+        let inverse = parent_transform.rotation.inverse();
+        let impact = (inverse * wrapped_delta(pos, *impact).extend(0.0)).truncate();
+        let direction = (inverse * direction.extend(0.0)).truncate();
+        let parent_centroid = polygon_centroid(parent_points);
+
+        for piece in fracture(parent_points, impact, direction, *amount) {
+            // fragments spin around their own centroid
+            let centroid = polygon_centroid(&piece);
+            let points: Vec<Vec2> = piece.iter().map(|p| *p - centroid).collect();
+            let bounding = polygon_radius(&points, Vec2::ZERO);
+
+            let away = (parent_transform.rotation * (centroid - parent_centroid).extend(0.0))
+                .truncate()
+                .try_normalize()
+                .unwrap_or_else(|| Vec2::from_angle(rng.gen_range(0.0..std::f32::consts::TAU)));
+            let center = parent_transform.translation
+                + parent_transform.rotation * centroid.extend(0.0)
+                + (away * SEPARATION_GAP).extend(0.0);
+            let vel = *parent_velocity + away * rng.gen_range(SEPARATION_IMPULSE);
 
+            let shape = shapes::Polygon {
+                points: points.clone(),
+                closed: true,
+            };
             let asteroid = commands
                 .spawn_bundle(AsteroidBundle {
                     shape: (GeometryBuilder::build_as(
-                        shape,
+                        &shape,
                         DrawMode::Outlined {
                             outline_mode: StrokeMode::new(LIGHT, POLY_LINE_WIDTH * 1.5),
                             fill_mode: FillMode::color(DARK),
                         },
-                        Transform::default().with_translation(vec3(
-                            if centroid.x.is_sign_positive() {
-                                pos.x - centroid.x
-                            } else {
-                                pos.x + centroid.x
-                            },
-                            if centroid.y.is_sign_positive() {
-                                pos.y - centroid.y
-                            } else {
-                                pos.y + centroid.y
-                            },
-                            1.0,
-                        )),
+                        Transform {
+                            translation: center,
+                            ..*parent_transform
+                        },
                     )),
                     bound: Bounding::from(bounding),
                     wrap: BoundaryWrap,
-                    vel: velocity(&centroid, &bounding, &mut rng),
-                    vel_limit: SpeedLimit::from(200.0),
+                    vel: Velocity::from(vel),
+                    vel_limit: SpeedLimit::from(config.asteroids.speed_limit),
                     ang_vel: AngularVelocity::from(rng.gen_range(0.1..1.0)),
                     marker: Asteroid,
-                    points: Points(points.clone()),
-                    health: health(&bounding),
+                    mass: Mass::polygon(&points, config.asteroids.density),
+                    points: Points(points),
+                    size: *size,
+                    health: size.health(),
                 })
                 .id();
 
@@ -269,14 +353,17 @@ pub fn asteroid_split_system(
 
 pub fn asteroid_generation_system(
     mut commands: Commands,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<AsteroidGeneration>>,
     mut ev_asteroid_spawn: EventReader<AsteroidSpawnEvent>,
+    director: Res<WaveDirector>,
+    config: Res<GameConfig>,
     debug: Res<Debug>,
 ) {
     for AsteroidSpawnEvent {
         amount,
         pos,
         radius,
+        size,
     } in ev_asteroid_spawn.iter()
     {
         for i in 0..*amount {
@@ -291,35 +378,23 @@ pub fn asteroid_generation_system(
 
             let angle_inc = 360.0 / edges as f32;
 
-            let (min, max) = match *radius as usize {
-                60..=80 => (60.0, 80.0),
-                30..=50 => (30.0, 50.0),
-                _ => (10.0, 20.0),
-            };
+            let roughness = size.roughness() as i32;
 
             for i in 1..=edges {
-                let r = match *radius as usize {
-                    60..=80 => rng.gen_range((*radius as i32 - 30)..=(*radius as i32)),
-                    30..=50 => rng.gen_range((*radius as i32 - 15)..=(*radius as i32)),
-                    _ => rng.gen_range((*radius as i32 - 3)..=(*radius as i32)),
-                } as f32;
+                let r = rng.gen_range((*radius as i32 - roughness)..=(*radius as i32)) as f32;
 
                 let angle = (angle_inc * i as f32).to_radians();
                 points.push(vec2(r * angle.sin(), r * angle.cos()));
             }
-            let p_len = points.len();
-            let average = points
-                .iter()
-                .map(|p| ((p.x).powi(2) + (p.y).powi(2)).sqrt())
-                .sum::<f32>()
-                / p_len as f32;
-            let bounding = average.clamp(min, max);
+            let bounding = polygon_radius(&points, Vec2::ZERO);
             let shape = shapes::Polygon {
                 points: points.clone(),
                 closed: true,
             };
 
             let center = vec3(pos.x, pos.y, 1.0);
+            let speed = rng.gen_range(director.config.speed.clone());
+            let vel = velocity(&vec2(center.x, center.y), size, &mut **rng).0 * speed;
 
             let asteroid = commands
                 .spawn_bundle(AsteroidBundle {
@@ -333,18 +408,20 @@ pub fn asteroid_generation_system(
                     )),
                     bound: Bounding::from(bounding),
                     wrap: BoundaryWrap,
-                    vel: velocity(&vec2(center.x, center.y), radius, &mut rng),
-                    vel_limit: SpeedLimit::from(200.0),
+                    vel: Velocity::from(vel),
+                    vel_limit: SpeedLimit::from(config.asteroids.speed_limit),
                     ang_vel: AngularVelocity::from(rng.gen_range(0.1..1.0)),
                     marker: Asteroid,
+                    mass: Mass::polygon(&points, config.asteroids.density),
                     points: Points(points),
-                    health: health(radius),
+                    size: *size,
+                    health: size.health(),
                 })
                 .id();
 
             if debug.0 {
                 let d_circle = shapes::Circle {
-                    radius: average,
+                    radius: bounding,
                     ..Default::default()
                 };
                 let debug_bound = commands
@@ -4,13 +4,116 @@ use crate::{
 };
 
 use super::Bounding;
-use bevy::prelude::*;
+use crate::spatial::minimum_image;
+use bevy::{math::ivec2, prelude::*, utils::HashSet};
+use bevy_prototype_lyon::{
+    entity::ShapeBundle,
+    prelude::{DrawMode, Path},
+};
+
+// the frame wraps into a torus of this size
+pub const FRAME_SIZE: Vec2 =
+    Vec2::from_array([FRAME_END_X - FRAME_START_X, FRAME_END_Y - FRAME_START_Y]);
+pub const FRAME_ORIGIN: Vec2 = Vec2::from_array([FRAME_START_X, FRAME_START_Y]);
+
+/**
+ * Shortest offset from a to b, going across the frame edges if that is closer
+ */
+pub fn wrapped_delta(a: Vec2, b: Vec2) -> Vec2 {
+    minimum_image(a, b, FRAME_SIZE)
+}
+
+pub fn wrapped_distance(a: Vec2, b: Vec2) -> f32 {
+    wrapped_delta(a, b).length()
+}
+
+/**
+ * Cuts the segment from a to b where it leaves the frame, moving the pieces past an edge in
+ * from the opposite side. a has to be inside the frame and the segment shorter than the frame.
+ */
+pub fn wrapped_segments(a: Vec2, b: Vec2) -> Vec<(Vec2, Vec2)> {
+    // how far along d a coordinate at p gets before leaving [start, end)
+    let exit = |p: f32, d: f32, start: f32, end: f32| {
+        if p + d >= end {
+            (end - p) / d
+        } else if p + d < start {
+            (start - p) / d
+        } else {
+            f32::INFINITY
+        }
+    };
+
+    let (mut a, mut b) = (a, b);
+    let mut segments = Vec::new();
+    // one crossing per axis at most
+    for _ in 0..2 {
+        let d = b - a;
+        let tx = exit(a.x, d.x, FRAME_START_X, FRAME_END_X);
+        let ty = exit(a.y, d.y, FRAME_START_Y, FRAME_END_Y);
+        let t = tx.min(ty);
+        if t > 1.0 {
+            break;
+        }
+
+        let crossing = a + d * t;
+        segments.push((a, crossing));
+        let mut shift = Vec2::ZERO;
+        if tx == t {
+            shift.x = -d.x.signum() * FRAME_SIZE.x;
+        }
+        if ty == t {
+            shift.y = -d.y.signum() * FRAME_SIZE.y;
+        }
+        a = crossing + shift;
+        b += shift;
+    }
+    segments.push((a, b));
+
+    segments
+}
 
 #[derive(Debug, Component)]
 pub struct BoundaryWrap;
 #[derive(Debug, Component, Default)]
 pub struct BoundaryRemoval;
 
+/**
+ * Copy of a wrapping entity drawn one frame width or height away, so the part hanging over
+ * an edge shows up on the opposite side
+ */
+#[derive(Debug, Component)]
+pub struct Ghost {
+    pub of: Entity,
+    // in frame sizes, each axis is -1, 0 or 1
+    pub side: IVec2,
+}
+
+// sides along one axis where a copy is needed, 0 being the entity itself
+fn axis_sides(c: f32, r: f32, start: f32, end: f32) -> Vec<i32> {
+    let mut sides = vec![0];
+    if c - r < start {
+        sides.push(1);
+    }
+    if c + r > end {
+        sides.push(-1);
+    }
+
+    sides
+}
+
+fn ghost_sides(pos: Vec3, r: f32) -> Vec<IVec2> {
+    let xs = axis_sides(pos.x, r, FRAME_START_X, FRAME_END_X);
+    let ys = axis_sides(pos.y, r, FRAME_START_Y, FRAME_END_Y);
+    xs.iter()
+        .flat_map(|x| ys.iter().map(|y| ivec2(*x, *y)))
+        .filter(|side| *side != IVec2::ZERO)
+        .collect()
+}
+
+fn ghost_translation(pos: Vec3, side: IVec2) -> Vec3 {
+    pos + (side.as_vec2() * FRAME_SIZE).extend(0.0)
+}
+
 pub fn boundary_removal_system(
     mut commands: Commands,
     mut query: Query<(Entity, &Transform, &Bounding, With<BoundaryRemoval>)>,
@@ -28,21 +131,153 @@ pub fn boundary_removal_system(
     }
 }
 
-pub fn boundary_wrapping_system(mut query: Query<(&mut Transform, &Bounding, With<BoundaryWrap>)>) {
-    for (mut transform, bound, _) in query.iter_mut() {
-        let r = bound.0;
+/**
+ * Moves entities a whole frame back once their center crosses an edge. The ghosts make up for
+ * the part still hanging over the edge.
+ */
+pub fn boundary_wrapping_system(mut query: Query<&mut Transform, With<BoundaryWrap>>) {
+    for mut transform in query.iter_mut() {
         let Vec3 { x, y, z: _ } = transform.translation;
 
-        if x > FRAME_END_X + r {
-            transform.translation.x = FRAME_START_X - r;
-        } else if x < FRAME_START_X - r {
-            transform.translation.x = FRAME_END_X + r;
+        if x >= FRAME_END_X {
+            transform.translation.x -= FRAME_SIZE.x;
+        } else if x < FRAME_START_X {
+            transform.translation.x += FRAME_SIZE.x;
+        }
+
+        if y >= FRAME_END_Y {
+            transform.translation.y -= FRAME_SIZE.y;
+        } else if y < FRAME_START_Y {
+            transform.translation.y += FRAME_SIZE.y;
+        }
+    }
+}
+
+/**
+ * Keeps a ghost on every side a wrapping entity overlaps, and removes the ones no longer needed
+ */
+#[allow(clippy::type_complexity)]
+pub fn ghost_system(
+    mut commands: Commands,
+    wrapping: Query<
+        (Entity, &Transform, &Bounding, &Path, &DrawMode, &Visibility),
+        (With<BoundaryWrap>, Without<Ghost>),
+    >,
+    mut ghosts: Query<(
+        Entity,
+        &Ghost,
+        &mut Transform,
+        &mut DrawMode,
+        &mut Visibility,
+        Without<BoundaryWrap>,
+    )>,
+) {
+    let mut existing = HashSet::new();
+    for (entity, ghost, mut transform, mut mode, mut visibility, _) in ghosts.iter_mut() {
+        let (_, source, bounding, _, source_mode, source_visibility) = match wrapping.get(ghost.of)
+        {
+            Ok(source) => source,
+            Err(_) => {
+                commands.entity(entity).despawn();
+                continue;
+            }
+        };
+        if !ghost_sides(source.translation, bounding.0).contains(&ghost.side) {
+            commands.entity(entity).despawn();
+            continue;
         }
 
-        if y > FRAME_END_Y + r {
-            transform.translation.y = FRAME_START_Y - r;
-        } else if y < FRAME_START_Y - r {
-            transform.translation.y = FRAME_END_Y + r;
+        transform.translation = ghost_translation(source.translation, ghost.side);
+        transform.rotation = source.rotation;
+        // only touch what changed, lyon rebuilds the mesh on every change
+        if *mode != *source_mode {
+            *mode = *source_mode;
+        }
+        if visibility.is_visible != source_visibility.is_visible {
+            visibility.is_visible = source_visibility.is_visible;
+        }
+        existing.insert((ghost.of, ghost.side));
+    }
+
+    for (entity, transform, bounding, path, mode, visibility) in wrapping.iter() {
+        for side in ghost_sides(transform.translation, bounding.0) {
+            if existing.contains(&(entity, side)) {
+                continue;
+            }
+
+            commands
+                .spawn_bundle(ShapeBundle {
+                    path: Path(path.0.clone()),
+                    mode: *mode,
+                    transform: Transform {
+                        translation: ghost_translation(transform.translation, side),
+                        ..*transform
+                    },
+                    visibility: visibility.clone(),
+                    ..default()
+                })
+                .insert(Ghost { of: entity, side });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::vec2;
+
+    fn inside(p: Vec2) -> bool {
+        let eps = 1e-3;
+        p.x >= FRAME_START_X - eps
+            && p.x <= FRAME_END_X + eps
+            && p.y >= FRAME_START_Y - eps
+            && p.y <= FRAME_END_Y + eps
+    }
+
+    #[test]
+    fn wrapped_segments_stay_in_the_frame_and_keep_their_length() {
+        for (a, b, pieces) in [
+            (vec2(0.0, 0.0), vec2(50.0, 20.0), 1),
+            (
+                vec2(FRAME_END_X - 10.0, 0.0),
+                vec2(FRAME_END_X + 30.0, 0.0),
+                2,
+            ),
+            (
+                vec2(FRAME_START_X + 10.0, 0.0),
+                vec2(FRAME_START_X - 30.0, 5.0),
+                2,
+            ),
+            (
+                vec2(0.0, FRAME_END_Y - 5.0),
+                vec2(10.0, FRAME_END_Y + 40.0),
+                2,
+            ),
+            (
+                vec2(0.0, FRAME_START_Y + 5.0),
+                vec2(-10.0, FRAME_START_Y - 40.0),
+                2,
+            ),
+            // out through the corner region, crossing both edges
+            (
+                vec2(FRAME_END_X - 10.0, FRAME_END_Y - 20.0),
+                vec2(FRAME_END_X + 30.0, FRAME_END_Y + 20.0),
+                3,
+            ),
+        ] {
+            let segments = wrapped_segments(a, b);
+            assert_eq!(segments.len(), pieces, "{a} to {b}: {segments:?}");
+            assert_eq!(segments[0].0, a);
+            let length: f32 = segments.iter().map(|(a, b)| a.distance(*b)).sum();
+            assert!(
+                (length - a.distance(b)).abs() < 1e-3,
+                "{a} to {b}: {segments:?}"
+            );
+            for (start, end) in &segments {
+                assert!(inside(*start) && inside(*end), "{a} to {b}: {segments:?}");
+                // every piece keeps the direction of the whole
+                assert!((*end - *start).dot(b - a) >= 0.0);
+            }
         }
     }
 }
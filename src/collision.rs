@@ -1,120 +1,243 @@
-use std::{f32::consts::PI, ops::Range, time::Duration};
+use std::{ops::Range, time::Duration};
 
 use crate::{
-    asteroid::{AsteroidSpawnEvent, AsteroidSplitEvent, Damage, Health, Points},
+    asteroid::Asteroid,
+    asteroid::{AsteroidSize, AsteroidSplitEvent, Damage, Health, Points},
+    boundary::{boundary_wrapping_system, FRAME_ORIGIN, FRAME_SIZE},
+    boundary::{wrapped_delta, wrapped_distance},
+    destruction_system,
+    geometry::{
+        circle_circle_contact, circle_polygon_contact, polygon_polygon_contact, world_polygon,
+        Contact,
+    },
+    hull::{Hull, Shield},
+    movement::{AngularVelocity, Mass},
+    score::ScoreEvent,
+    spatial::{SpatialGrid, GRID_CELL_SIZE},
+    timestep::SimulationApp,
     weapons::Bullet,
-    Flick,
+    Flick, GameSystem,
 };
 
 use super::{
-    random::Random, Asteroid, DestructionEvent, GrainParticleSpawnEvent, PlayerDeathEvent, Ship,
-    ShipState, Velocity, ASTEROID_SIZES,
+    DestructionEvent, GrainParticleSpawnEvent, PlayerDeathEvent, Ship, ShipState, Velocity,
 };
+use bevy::ecs::component::Component;
 use bevy::math::vec2;
 use bevy::prelude::*;
-use bevy::{ecs::component::Component, math::vec3};
 use derive_more::From;
-use rand::Rng;
+
+/**
+ * Finds touching bodies through the spatial grid, bounces them off each other and deals the
+ * damage of the hits
+ */
+pub struct CollisionPlugin;
+
+impl Plugin for CollisionPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpatialGrid::new(GRID_CELL_SIZE, FRAME_ORIGIN, FRAME_SIZE))
+            .add_event::<DestructionEvent>()
+            .add_event::<PlayerDeathEvent>()
+            .add_simulation_system(
+                spatial_grid_system
+                    .after(GameSystem::Boundary)
+                    .after(boundary_wrapping_system)
+                    .before(GameSystem::Collision),
+            )
+            .add_simulation_system_set(
+                SystemSet::new()
+                    .label(GameSystem::Collision)
+                    .with_system(
+                        impact_damage_system::<Asteroid>
+                            .before(elastic_collision_system::<Ship, Asteroid>),
+                    )
+                    .with_system(elastic_collision_system::<Asteroid, Bullet>)
+                    .with_system(elastic_collision_system::<Ship, Asteroid>)
+                    .with_system(self_collision_system::<Asteroid>)
+                    .with_system(damage_transfer_system::<Bullet, Asteroid>)
+                    .with_system(damage_transfer_system::<Ship, Asteroid>)
+                    .after(GameSystem::Boundary),
+            )
+            .add_simulation_system(destruction_system.after(GameSystem::Collision));
+    }
+}
 
 fn distance_between(a: &Vec3, b: &Vec3) -> f32 {
-    a.distance(*b)
+    wrapped_distance(a.truncate(), b.truncate())
 }
 
 fn circles_touching(a: &Transform, ar: &Bounding, b: &Transform, br: &Bounding) -> bool {
     distance_between(&a.translation, &b.translation) < (ar.0 + br.0)
 }
 
-fn distance_to_move(a: &Vec3, ar: f32, b: &Vec3, br: f32) -> f32 {
-    ar + br - distance_between(a, b)
-}
+/**
+ * Narrow phase for bodies that pass the circle test. Bodies with Points are tested by their
+ * outline, everything else by its bounding circle.
+ */
+pub fn contact(
+    a: &Transform,
+    ab: &Bounding,
+    ap: Option<&Points>,
+    b: &Transform,
+    bb: &Bounding,
+    bp: Option<&Points>,
+) -> Option<Contact> {
+    if !circles_touching(a, ab, b, bb) {
+        return None;
+    }
 
-// c: contact angle
-fn x_vel_comp_after_collision(v1: f32, m1: f32, a1: f32, v2: f32, m2: f32, a2: f32, c: f32) -> f32 {
-    ((v1 * f32::cos(a1 - c) * (m1 - m2) + 2.0 * m2 * v2 * f32::cos(a2 - c)) / (m1 + m2))
-        * f32::cos(c)
-        + v1 * f32::sin(a1 - c) * f32::cos(c + PI / 2.0)
+    let ac = a.translation.truncate();
+    // b as seen from a, across the frame edges if that is closer
+    let bc = ac + wrapped_delta(ac, b.translation.truncate());
+    let b = &Transform {
+        translation: bc.extend(b.translation.z),
+        ..*b
+    };
+    match (ap, bp) {
+        (None, None) => circle_circle_contact(ac, ab.0, bc, bb.0),
+        (None, Some(bp)) => circle_polygon_contact(ac, ab.0, &world_polygon(&bp.0, b)),
+        (Some(ap), None) => {
+            circle_polygon_contact(bc, bb.0, &world_polygon(&ap.0, a)).map(Contact::flipped)
+        }
+        (Some(ap), Some(bp)) => {
+            polygon_polygon_contact(&world_polygon(&ap.0, a), &world_polygon(&bp.0, b))
+        }
+    }
 }
 
-fn y_vel_comp_after_collision(v1: f32, m1: f32, a1: f32, v2: f32, m2: f32, a2: f32, c: f32) -> f32 {
-    ((v1 * f32::cos(a1 - c) * (m1 - m2) + 2.0 * m2 * v2 * f32::cos(a2 - c)) / (m1 + m2))
-        * f32::sin(c)
-        + v1 * f32::sin(a1 - c) * f32::sin(c + PI / 2.0)
+/**
+ * Orders a grid pair so that its first entity belongs to the first query
+ */
+fn oriented(
+    (x, y): (Entity, Entity),
+    first: impl Fn(Entity) -> bool,
+    second: impl Fn(Entity) -> bool,
+) -> Option<(Entity, Entity)> {
+    if first(x) && second(y) {
+        Some((x, y))
+    } else if first(y) && second(x) {
+        Some((y, x))
+    } else {
+        None
+    }
 }
 
-fn vel_after_collision(v1: f32, m1: f32, a1: f32, v2: f32, m2: f32, a2: f32, c: f32) -> Vec2 {
-    vec2(
-        x_vel_comp_after_collision(v1, m1, a1, v2, m2, a2, c),
-        y_vel_comp_after_collision(v1, m1, a1, v2, m2, a2, c),
-    )
+/**
+ * Rebuilds the broad phase from this frame's positions, before any collision system runs
+ */
+pub fn spatial_grid_system(
+    mut grid: ResMut<SpatialGrid>,
+    bodies: Query<(Entity, &Transform, &Bounding)>,
+) {
+    grid.clear();
+    for (entity, transform, bounding) in bodies.iter() {
+        grid.insert(entity, transform.translation.truncate(), bounding.0);
+    }
+    grid.build_pairs();
 }
 
-fn rotate_point(fp: Vec2, pt: Vec2, a: f32) -> Vec2 {
-    let x = pt.x - fp.x;
-    let y = pt.y - fp.y;
-    let x_rot = x * a.cos() + y * a.sin();
-    let y_rot = y * a.cos() - x * a.sin();
-
-    vec2(fp.x + x_rot, fp.y + y_rot)
+// share of the closing speed kept after a hit
+const RESTITUTION: f32 = 0.992;
+// how much the surfaces grip, turning glancing hits into spin
+const FRICTION: f32 = 0.3;
+
+/**
+ * A body as seen by the collision response
+ */
+#[derive(Debug, Clone, Copy)]
+struct Body {
+    velocity: Vec2,
+    spin: f32,
+    inv_mass: f32,
+    // zero for bodies that can't spin
+    inv_inertia: f32,
+    // from the body's origin to the contact point
+    arm: Vec2,
 }
 
-// source: https://github.com/williamfiset/Algorithms/blob/master/src/main/java/com/williamfiset/algorithms/geometry/CircleCircleIntersectionPoints.js
-fn circle_impact_position(a: &Transform, b: &Transform, ar: f32, br: f32) -> Option<Vec3> {
-    let mut r: f32 = 0.0;
-    let mut R: f32 = 0.0;
-    let mut cx: f32 = 0.0;
-    let mut Cx: f32 = 0.0;
-    let mut cy: f32 = 0.0;
-    let mut Cy: f32 = 0.0;
-    if ar < br {
-        r = ar;
-        R = br;
-        cx = a.translation.x;
-        cy = a.translation.y;
-        Cx = b.translation.x;
-        Cy = b.translation.y;
-    } else {
-        r = ar;
-        R = br;
-        Cx = b.translation.x;
-        Cy = b.translation.y;
-        cx = a.translation.x;
-        cy = a.translation.y;
+impl Body {
+    fn new(velocity: Vec2, spin: Option<f32>, mass: &Mass, arm: Vec2) -> Self {
+        Body {
+            velocity,
+            spin: spin.unwrap_or(0.0),
+            inv_mass: 1.0 / mass.mass,
+            inv_inertia: spin.map(|_| 1.0 / mass.inertia).unwrap_or(0.0),
+            arm,
+        }
     }
 
-    let d = distance_between(&a.translation, &b.translation);
-
-    if d < f32::EPSILON && (R - r).abs() < f32::EPSILON {
-        return None;
-    }
-    // No intersection (circles centered at the
-    // same place with different size)
-    else if d < f32::EPSILON {
-        return None;
+    fn contact_velocity(&self) -> Vec2 {
+        self.velocity + self.spin * self.arm.perp()
     }
 
-    let dx = cx - Cx;
-    let dy = cy - Cy;
-    let x = (dx / d) * R + Cx;
-    let y = (dy / d) * R + Cy;
-    let P = vec2(x, y);
+    // how much an impulse along direction is resisted at the contact point
+    fn inv_effective_mass(&self, direction: Vec2) -> f32 {
+        self.inv_mass + self.arm.perp_dot(direction).powi(2) * self.inv_inertia
+    }
 
-    if (ar - br).abs() - d < f32::EPSILON || (ar - br + d).abs() < f32::EPSILON {
-        return Some(vec3(P.x, P.y, 1.0));
+    fn apply(&mut self, impulse: Vec2) {
+        self.velocity += impulse * self.inv_mass;
+        self.spin += self.arm.perp_dot(impulse) * self.inv_inertia;
     }
+}
 
-    // No intersection. Either the small circle contained within
-    // big circle or circles are simply disjoint.
-    if (d + r) < R || (R + r < d) {
-        return None;
-    };
+/**
+ * Pushes two touching bodies apart at the contact point, off-center hits and friction along
+ * the surfaces set them spinning. Bodies already moving apart are left alone.
+ */
+fn resolve_collision(contact: &Contact, a: &mut Body, b: &mut Body) {
+    let normal = contact.normal;
+    let closing = (b.contact_velocity() - a.contact_velocity()).dot(normal);
+    if closing >= 0.0 {
+        return;
+    }
 
-    let C = vec2(Cx, Cy);
-    let angle = ((r * r - d * d - R * R) / (-2.0 * d * R)).acos();
-    let pt1 = rotate_point(C, P, angle.abs());
-    let pt2 = rotate_point(C, P, -angle);
-    let avg = vec3((pt1.x + pt2.x) / 2.0, (pt1.y + pt2.y) / 2.0, 1.0);
+    let impulse = -(1.0 + RESTITUTION) * closing
+        / (a.inv_effective_mass(normal) + b.inv_effective_mass(normal));
+    a.apply(-normal * impulse);
+    b.apply(normal * impulse);
+
+    // friction can at most stop the surfaces from sliding
+    let relative = b.contact_velocity() - a.contact_velocity();
+    if let Some(tangent) = (relative - normal * relative.dot(normal)).try_normalize() {
+        let friction = (relative.dot(tangent)
+            / (a.inv_effective_mass(tangent) + b.inv_effective_mass(tangent)))
+        .min(FRICTION * impulse);
+        a.apply(tangent * friction);
+        b.apply(-tangent * friction);
+    }
+}
 
-    return Some(avg);
+/**
+ * Runs the collision response for a and b, b having been moved next to a if they touch
+ * across a frame edge
+ */
+#[allow(clippy::too_many_arguments)]
+fn collide(
+    contact: &Contact,
+    at: &Transform,
+    av: &mut Velocity,
+    aw: Option<Mut<AngularVelocity>>,
+    am: &Mass,
+    bt: &Transform,
+    bv: &mut Velocity,
+    bw: Option<Mut<AngularVelocity>>,
+    bm: &Mass,
+) {
+    let ac = at.translation.truncate();
+    let bc = ac + wrapped_delta(ac, bt.translation.truncate());
+    let mut a = Body::new(av.0, aw.as_ref().map(|w| w.0), am, contact.point - ac);
+    let mut b = Body::new(bv.0, bw.as_ref().map(|w| w.0), bm, contact.point - bc);
+    resolve_collision(contact, &mut a, &mut b);
+
+    av.0 = a.velocity;
+    bv.0 = b.velocity;
+    if let Some(mut aw) = aw {
+        aw.0 = a.spin;
+    }
+    if let Some(mut bw) = bw {
+        bw.0 = b.spin;
+    }
 }
 
 #[derive(Debug, Component, Default, Deref, DerefMut, From)]
@@ -123,68 +246,62 @@ pub struct Bounding(pub f32);
 const IMPACT_VEL_PARTICLE_TRIGGER: f32 = 25.0;
 const IMPACT_SPAWN_RADIUS: f32 = 1.0;
 const IMPCAT_PARTICLE_RANGE: Range<i32> = 1..5;
-// Temporarily Radius will act as Mass for momentum calculation
+#[allow(clippy::type_complexity)]
 pub fn self_collision_system<A: Component>(
-    mut colliders: Query<(&mut Transform, &Bounding, &mut Velocity, With<A>)>,
+    mut colliders: Query<(
+        &mut Transform,
+        &Bounding,
+        &mut Velocity,
+        Option<&mut AngularVelocity>,
+        &Mass,
+        Option<&Points>,
+        With<A>,
+    )>,
+    grid: Res<SpatialGrid>,
     mut ev_grain: EventWriter<GrainParticleSpawnEvent>,
 ) {
-    let mut combinations = colliders.iter_combinations_mut();
-    while let Some([a, b]) = combinations.fetch_next() {
-        let (at, ab, mut av, _) = a;
-        let Vec3 { x: ax, y: ay, z: _ } = at.translation;
-        let ar = ab.0; // radius
-        let (mut bt, bb, mut bv, _) = b;
-        let Vec3 { x: bx, y: by, z: _ } = bt.translation;
-        let br = bb.0; // radius
-
-        if circles_touching(&at, ab, &bt, bb) {
-            let contact_angle = f32::atan2(by - ay, bx - ax);
-            let distance_to_move = distance_to_move(&at.translation, ar, &bt.translation, br);
-
-            // velocities
-            let v1 = av.length();
-            let v2 = bv.length();
-
-            if v1 > IMPACT_VEL_PARTICLE_TRIGGER || v2 > IMPACT_VEL_PARTICLE_TRIGGER {
-                if let Some(impact_pos) = circle_impact_position(&at, &bt, ab.0, bb.0) {
-                    ev_grain.send(GrainParticleSpawnEvent {
-                        pos: impact_pos,
-                        spawn_radius: IMPACT_SPAWN_RADIUS,
-                        particles: IMPCAT_PARTICLE_RANGE,
-                        impact_vel: vec2(0.0, 0.0),
-                    });
-                }
+    for &(a, b) in grid.pairs() {
+        let [a, b] = match colliders.get_many_mut([a, b]) {
+            Ok(pair) => pair,
+            Err(_) => continue,
+        };
+        let (at, ab, mut av, aw, am, ap, _) = a;
+        let (mut bt, bb, mut bv, bw, bm, bp, _) = b;
+
+        if let Some(contact) = contact(&at, ab, ap, &bt, bb, bp) {
+            if av.length() > IMPACT_VEL_PARTICLE_TRIGGER
+                || bv.length() > IMPACT_VEL_PARTICLE_TRIGGER
+            {
+                ev_grain.send(GrainParticleSpawnEvent {
+                    pos: contact.point.extend(1.0),
+                    spawn_radius: IMPACT_SPAWN_RADIUS,
+                    particles: IMPCAT_PARTICLE_RANGE,
+                    impact_vel: vec2(0.0, 0.0),
+                });
             }
 
-            // move the second circle
-            bt.translation.x += f32::cos(contact_angle) * distance_to_move;
-            bt.translation.y += f32::sin(contact_angle) * distance_to_move;
+            collide(&contact, &at, &mut av, aw, am, &bt, &mut bv, bw, bm);
 
-            // masses
-            let m1 = PI * ar.powi(2);
-            let m2 = PI * br.powi(2);
-            // angles
-            let a1 = av.angle_between(vec2(ax, ay));
-            let a2 = bv.angle_between(vec2(bx, by));
-
-            av.0 = vel_after_collision(v1, m1, a1, v2, m2, a2, contact_angle) * 0.992;
-            bv.0 = vel_after_collision(v2, m2, a2, v1, m1, a1, contact_angle) * 0.992;
+            // move the second body out of the first
+            bt.translation += (contact.normal * contact.depth).extend(0.0);
         }
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn damage_transfer_system<Dealer: Component, Victim: Component>(
     mut ev_grain: EventWriter<GrainParticleSpawnEvent>,
     // mut ev_ball_particles: EventWriter<BallParticleSpawnEvent>,
     mut ev_destruction: EventWriter<DestructionEvent>,
-    mut ev_asteroid_spawn: EventWriter<AsteroidSpawnEvent>,
+    mut ev_asteroid_split: EventWriter<AsteroidSplitEvent>,
+    mut ev_score: EventWriter<ScoreEvent>,
     mut victims: Query<(
         Entity,
         &Velocity,
         &Transform,
         &Bounding,
         &mut Health,
-        Option<&Asteroid>,
+        Option<&AsteroidSize>,
         Option<&Points>,
         With<Victim>,
     )>,
@@ -195,58 +312,47 @@ pub fn damage_transfer_system<Dealer: Component, Victim: Component>(
         &Bounding,
         &Damage,
         Option<&Bullet>,
+        Option<&Points>,
         With<Dealer>,
     )>,
-    mut rng: Local<Random>,
+    grid: Res<SpatialGrid>,
     mut commands: Commands,
 ) {
-    for (victim, vv, vt, vb, mut health, asteroid, points, _) in victims.iter_mut() {
-        let Vec3 { x: x1, y: y1, z: _ } = vt.translation;
-        for (dealer, dv, dt, db, damage, bullet, _) in dealers.iter_mut() {
-            let Vec3 { x: x2, y: y2, z: _ } = dt.translation;
-            if circles_touching(&vt, vb, &dt, db) {
+    for &pair in grid.pairs() {
+        let (victim, dealer) =
+            match oriented(pair, |e| victims.contains(e), |e| dealers.contains(e)) {
+                Some(pair) => pair,
+                None => continue,
+            };
+        if let (Ok(victim), Ok(dealer)) = (victims.get_mut(victim), dealers.get_mut(dealer)) {
+            let (victim, vv, vt, vb, mut health, size, points, _) = victim;
+            let (dealer, dv, dt, db, damage, bullet, dealer_points, _) = dealer;
+            if health.0 < 0.0 {
+                // already destroyed by another dealer this frame
+                continue;
+            }
+            if let Some(contact) = contact(dt, db, dealer_points, vt, vb, points) {
+                let impact_pos = contact.point.extend(1.0);
                 let new_health = health.0 - damage.0;
                 if new_health < 0.0 {
+                    health.0 = new_health;
                     ev_destruction.send(DestructionEvent { entity: victim });
-                    if let Some(Asteroid) = asteroid {
-                        // let points = points.unwrap();
-                        match vb.0 as usize {
-                            60..=80 => {
-                                // ev_ball_particles.send(BallParticleSpawnEvent {
-                                //     pos: vt.translation,
-                                //     spawn_radius: vb.0 * 1.25,
-                                //     ball_radius: 30.0,
-                                //     particles: 20..30,
-                                //     delay: 30,
-                                //     dir_vel: dv.0,
-                                // });
-                                ev_asteroid_spawn.send(AsteroidSpawnEvent {
-                                    // parent_points: points.0.clone(),
-                                    amount: 3,
-                                    pos: vec2(vt.translation.x, vt.translation.y),
-                                    radius: rng.gen_range(ASTEROID_SIZES.1),
-                                });
-                                ev_grain.send(GrainParticleSpawnEvent {
-                                    pos: vt.translation,
-                                    spawn_radius: vb.0 / 1.5,
-                                    particles: 100..200,
-                                    impact_vel: vec2(0.0, 0.0),
-                                });
-                            }
-                            30..=50 => {
-                                // ev_ball_particles.send(BallParticleSpawnEvent {
-                                //     pos: vt.translation,
-                                //     spawn_radius: vb.0 * 1.25,
-                                //     ball_radius: 18.0,
-                                //     particles: 10..20,
-                                //     delay: 30,
-                                //     dir_vel: dv.0,
-                                // });
-                                ev_asteroid_spawn.send(AsteroidSpawnEvent {
-                                    // parent_points: points.0.clone(),
+                    if let Some(size) = size {
+                        ev_score.send(ScoreEvent {
+                            points: size.score(),
+                        });
+                        match (size.fragments(), points) {
+                            (Some(fragments), Some(points)) => {
+                                ev_asteroid_split.send(AsteroidSplitEvent {
+                                    parent_points: points.0.clone(),
+                                    parent_transform: *vt,
+                                    parent_velocity: vv.0,
+                                    impact: contact.point,
+                                    direction: (dv.0 - vv.0)
+                                        .try_normalize()
+                                        .unwrap_or(contact.normal),
+                                    size: fragments,
                                     amount: 3,
-                                    pos: vec2(vt.translation.x, vt.translation.y),
-                                    radius: rng.gen_range(ASTEROID_SIZES.2),
                                 });
                                 ev_grain.send(GrainParticleSpawnEvent {
                                     pos: vt.translation,
@@ -257,14 +363,14 @@ pub fn damage_transfer_system<Dealer: Component, Victim: Component>(
                             }
                             _ => {
                                 ev_grain.send(GrainParticleSpawnEvent {
-                                    pos: dt.translation,
+                                    pos: impact_pos,
                                     spawn_radius: db.0,
                                     particles: 3..15,
                                     impact_vel: -(dv.0 / 4.0),
                                 });
                                 // hack. need to add weight to impacters
                                 ev_grain.send(GrainParticleSpawnEvent {
-                                    pos: dt.translation,
+                                    pos: impact_pos,
                                     spawn_radius: db.0,
                                     particles: 30..70,
                                     impact_vel: vec2((dv.x / 3.0), (dv.y / 3.0)),
@@ -276,7 +382,7 @@ pub fn damage_transfer_system<Dealer: Component, Victim: Component>(
                     health.0 = new_health;
 
                     ev_grain.send(GrainParticleSpawnEvent {
-                        pos: dt.translation,
+                        pos: impact_pos,
                         spawn_radius: db.0,
                         particles: 3..15,
                         impact_vel: -(dv.0 / 4.0),
@@ -295,82 +401,123 @@ pub fn damage_transfer_system<Dealer: Component, Victim: Component>(
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn elastic_collision_system<A: Component, B: Component>(
-    mut colliders: Query<(&mut Transform, &Bounding, &mut Velocity, With<A>)>,
+    mut colliders: Query<(
+        &mut Transform,
+        &Bounding,
+        &mut Velocity,
+        Option<&mut AngularVelocity>,
+        &Mass,
+        Option<&Points>,
+        With<A>,
+    )>,
     mut victims: Query<(
         &mut Transform,
         &Bounding,
         &mut Velocity,
+        Option<&mut AngularVelocity>,
+        &Mass,
+        Option<&Points>,
         With<B>,
         Without<A>,
     )>,
+    grid: Res<SpatialGrid>,
     mut ev_grain: EventWriter<GrainParticleSpawnEvent>,
 ) {
-    for (at, ab, mut av, _) in colliders.iter_mut() {
-        for (mut bt, bb, mut bv, _, _) in victims.iter_mut() {
-            let Vec3 { x: ax, y: ay, z: _ } = at.translation;
-            let ar = ab.0; // radius
-            let Vec3 { x: bx, y: by, z: _ } = bt.translation;
-            let br = bb.0; // radius
-
-            if circles_touching(&at, ab, &bt, bb) {
-                let contact_angle = f32::atan2(ay - by, ax - bx);
-                let distance_to_move = distance_to_move(&at.translation, ar, &bt.translation, br);
-
-                // velocities
-                let v1 = av.length();
-                let v2 = bv.length();
-
-                if v1 > IMPACT_VEL_PARTICLE_TRIGGER || v2 > IMPACT_VEL_PARTICLE_TRIGGER {
-                    if let Some(impact_pos) = circle_impact_position(&at, &bt, ab.0, bb.0) {
-                        ev_grain.send(GrainParticleSpawnEvent {
-                            pos: impact_pos,
-                            spawn_radius: IMPACT_SPAWN_RADIUS,
-                            particles: IMPCAT_PARTICLE_RANGE,
-                            impact_vel: vec2(0.0, 0.0),
-                        });
-                    }
+    for &pair in grid.pairs() {
+        let (a, b) = match oriented(pair, |e| colliders.contains(e), |e| victims.contains(e)) {
+            Some(pair) => pair,
+            None => continue,
+        };
+        if let (Ok(a), Ok(b)) = (colliders.get_mut(a), victims.get_mut(b)) {
+            let (at, ab, mut av, aw, am, ap, _) = a;
+            let (mut bt, bb, mut bv, bw, bm, bp, _, _) = b;
+
+            if let Some(contact) = contact(&at, ab, ap, &bt, bb, bp) {
+                if av.length() > IMPACT_VEL_PARTICLE_TRIGGER
+                    || bv.length() > IMPACT_VEL_PARTICLE_TRIGGER
+                {
+                    ev_grain.send(GrainParticleSpawnEvent {
+                        pos: contact.point.extend(1.0),
+                        spawn_radius: IMPACT_SPAWN_RADIUS,
+                        particles: IMPCAT_PARTICLE_RANGE,
+                        impact_vel: vec2(0.0, 0.0),
+                    });
                 }
 
-                // move the second circle
-                bt.translation.x += f32::cos(contact_angle) * distance_to_move;
-                bt.translation.y += f32::sin(contact_angle) * distance_to_move;
-
-                // masses
-                let m1 = PI * ar.powi(2);
-                let m2 = PI * br.powi(2);
-                // angles
-                let a1 = av.angle_between(vec2(ax, ay));
-                let a2 = bv.angle_between(vec2(bx, by));
+                collide(&contact, &at, &mut av, aw, am, &bt, &mut bv, bw, bm);
 
-                av.0 = vel_after_collision(v1, m1, a1, v2, m2, a2, contact_angle) * 0.992;
-                bv.0 = vel_after_collision(v2, m2, a2, v1, m1, a1, contact_angle) * 0.992;
+                // move the second body out of the first
+                bt.translation += (contact.normal * contact.depth).extend(0.0);
             }
         }
     }
 }
 
-pub fn kill_collision_system<A: Component, B: Component>(
+const IMPACT_DAMAGE_SCALE: f32 = 1.0 / 25_000.0;
+/**
+ * Damages the shield, then the hull, of ships hit by A. The damage scales with the
+ * closing speed of the impact and the mass of A.
+ */
+#[allow(clippy::type_complexity)]
+pub fn impact_damage_system<A: Component>(
     mut ev_explode: EventWriter<GrainParticleSpawnEvent>,
     mut ev_player_death: EventWriter<PlayerDeathEvent>,
-    colliders: Query<(Entity, &Transform, &Bounding, &Velocity, With<A>)>,
-    mut victims: Query<(&Transform, &Bounding, With<B>, Option<&mut Ship>)>,
+    colliders: Query<(
+        &Transform,
+        &Bounding,
+        &Velocity,
+        &Mass,
+        Option<&Points>,
+        With<A>,
+    )>,
+    mut ships: Query<(
+        &Ship,
+        &Transform,
+        &Bounding,
+        &Velocity,
+        Option<&Points>,
+        &mut Hull,
+        &mut Shield,
+    )>,
+    grid: Res<SpatialGrid>,
 ) {
-    for (_collider, at, ab, avel, _) in colliders.iter() {
-        for (bt, bb, _, ship) in victims.iter_mut() {
-            let r2 = bb.0;
-            if circles_touching(&at, ab, &bt, bb) {
-                if let Some(ship) = ship {
-                    if matches!(ship.state, ShipState::Alive) {
-                        ev_explode.send(GrainParticleSpawnEvent {
-                            pos: bt.translation,
-                            spawn_radius: r2,
-                            particles: 150..200,
-                            impact_vel: vec2(avel.x, avel.y),
-                        });
-                        ev_player_death.send(PlayerDeathEvent {});
-                    }
-                }
+    for (ship, st, sb, sv, sp, mut hull, mut shield) in ships.iter_mut() {
+        if !matches!(ship.state, ShipState::Alive) {
+            continue;
+        }
+
+        for collider in grid.query(st.translation.truncate(), sb.0) {
+            let (at, ab, av, am, ap, _) = match colliders.get(collider) {
+                Ok(collider) => collider,
+                Err(_) => continue,
+            };
+            let contact = match contact(st, sb, sp, at, ab, ap) {
+                Some(contact) => contact,
+                None => continue,
+            };
+
+            // the normal points from the ship into the collider
+            let closing_speed = (sv.0 - av.0).dot(contact.normal);
+            if closing_speed <= 0.0 {
+                // already moving apart
+                continue;
+            }
+
+            let damage = closing_speed * am.mass * IMPACT_DAMAGE_SCALE;
+            hull.integrity -= shield.absorb(damage);
+
+            if hull.integrity <= 0.0 {
+                hull.integrity = 0.0;
+                ev_explode.send(GrainParticleSpawnEvent {
+                    pos: st.translation,
+                    spawn_radius: sb.0,
+                    particles: 150..200,
+                    impact_vel: av.0,
+                });
+                ev_player_death.send(PlayerDeathEvent {});
+                break;
             }
         }
     }
@@ -0,0 +1,496 @@
+use std::{
+    fmt, fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    asset::{AssetLoader, AssetServerSettings, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_prototype_lyon::prelude::tess::math::Angle;
+use serde::Deserialize;
+
+use crate::{
+    asteroid::{Asteroid, AsteroidSize, Points, ASTEROID_SIZES},
+    movement::{Damping, Drive, Mass, SideThrusters, SpeedLimit, Stabilizer, SteeringControl},
+    particles::{
+        EXHAUST_LIVE_TIME, EXHAUST_TIMEOUT, GAS_EXHAUST_LIVE_TIME, GAS_EXHAUST_TIMEOUT,
+        GRAIN_RADIUS, PARTICLE_DAMPING,
+    },
+    weapons::{Heat, Loadout, Pattern, Projectile, Weapon, CANNON_BULLET_RADIUS},
+    Ship, PLAYER_DAMPING, PLAYER_SIZE,
+};
+
+pub const DEFAULT_CONFIG_PATH: &str = "assets/config.ron";
+
+/**
+ * Gameplay tuning. Every field is optional in the config file and falls back to the
+ * built in default.
+ */
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[serde(default, deny_unknown_fields)]
+#[uuid = "dac1f21c-6cb2-4bca-8ec6-963ec432370b"]
+pub struct GameConfig {
+    pub ship: ShipConfig,
+    // the ship's weapon slots, cycled through in this order
+    pub weapons: Vec<Weapon>,
+    pub asteroids: AsteroidConfig,
+    pub particles: ParticleConfig,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            ship: ShipConfig::default(),
+            weapons: vec![
+                Weapon::default(),
+                Weapon {
+                    name: "RAIL".to_string(),
+                    damage: 25.0,
+                    speed: 700.0,
+                    lifetime: 0.8,
+                    fire_rate: 2.0,
+                    spread: 0.0,
+                    heat: 0.3,
+                    density: 0.25,
+                    projectile: Projectile::Bolt { length: 8.0 },
+                    pattern: Pattern::default(),
+                },
+                Weapon {
+                    name: "SPRAY".to_string(),
+                    damage: 4.0,
+                    speed: 350.0,
+                    lifetime: 0.6,
+                    fire_rate: 15.0,
+                    spread: 12.0,
+                    heat: 0.04,
+                    density: 1.0,
+                    projectile: Projectile::Round {
+                        radius: CANNON_BULLET_RADIUS,
+                    },
+                    pattern: Pattern::default(),
+                },
+                Weapon {
+                    name: "FAN".to_string(),
+                    damage: 6.0,
+                    speed: 400.0,
+                    lifetime: 0.9,
+                    fire_rate: 2.5,
+                    spread: 0.0,
+                    heat: 0.15,
+                    density: 1.0,
+                    projectile: Projectile::Round {
+                        radius: CANNON_BULLET_RADIUS,
+                    },
+                    pattern: Pattern {
+                        count: 5,
+                        arc: 40.0,
+                        ..Pattern::default()
+                    },
+                },
+                Weapon {
+                    name: "BURST".to_string(),
+                    damage: 10.0,
+                    speed: 450.0,
+                    lifetime: 1.0,
+                    fire_rate: 2.5,
+                    spread: 2.0,
+                    heat: 0.05,
+                    density: 1.0,
+                    projectile: Projectile::Round {
+                        radius: CANNON_BULLET_RADIUS,
+                    },
+                    pattern: Pattern {
+                        burst: 4,
+                        burst_interval: 0.06,
+                        muzzle_offset: 0.5,
+                        ..Pattern::default()
+                    },
+                },
+                Weapon {
+                    name: "LASER".to_string(),
+                    damage: 25.0,
+                    heat: 0.6,
+                    projectile: Projectile::Beam { range: 300.0 },
+                    ..Weapon::default()
+                },
+                Weapon {
+                    name: "HOMING".to_string(),
+                    damage: 30.0,
+                    speed: 300.0,
+                    lifetime: 3.0,
+                    fire_rate: 1.5,
+                    heat: 0.25,
+                    projectile: Projectile::Missile {
+                        length: 6.0,
+                        thrust: 6.0,
+                        turn_rate: 180.0,
+                        lock_cone: 30.0,
+                        lock_range: 300.0,
+                        blast_radius: 40.0,
+                    },
+                    ..Weapon::default()
+                },
+            ],
+            asteroids: AsteroidConfig::default(),
+            particles: ParticleConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShipConfig {
+    // fraction of the velocity kept every 1/60th of a second
+    pub damping: f32,
+    pub speed_limit: f32,
+    // in degrees per second
+    pub steering: f32,
+    // how fast steering takes over the ship's spin, in degrees per second, per second
+    pub stabilizer: f32,
+    pub propulsion_force: f32,
+    pub reverse_force: f32,
+    pub side_thruster_force: f32,
+    // dealt to asteroids the ship rams
+    pub damage: f32,
+    // mass per unit of area
+    pub density: f32,
+    // fraction of the weapon heat gauge shed per second
+    pub cooling: f32,
+}
+
+impl Default for ShipConfig {
+    fn default() -> Self {
+        ShipConfig {
+            damping: PLAYER_DAMPING,
+            speed_limit: 200.0,
+            steering: 180.0,
+            stabilizer: 1440.0,
+            propulsion_force: 3.0,
+            reverse_force: 2.5,
+            side_thruster_force: 2.0,
+            damage: 5.0,
+            density: 1.0,
+            cooling: 0.35,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AsteroidConfig {
+    // radius ranges
+    pub large: RangeInclusive<f32>,
+    pub medium: RangeInclusive<f32>,
+    pub small: RangeInclusive<f32>,
+    pub speed_limit: f32,
+    pub density: f32,
+}
+
+impl Default for AsteroidConfig {
+    fn default() -> Self {
+        AsteroidConfig {
+            large: ASTEROID_SIZES.0,
+            medium: ASTEROID_SIZES.1,
+            small: ASTEROID_SIZES.2,
+            speed_limit: 200.0,
+            density: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParticleConfig {
+    pub grain_radius: f32,
+    // fraction of the velocity kept every 1/60th of a second
+    pub damping: f32,
+    // in seconds
+    pub exhaust_interval: f32,
+    pub exhaust_lifetime: f32,
+    pub gas_exhaust_interval: f32,
+    pub gas_exhaust_lifetime: f32,
+}
+
+impl Default for ParticleConfig {
+    fn default() -> Self {
+        ParticleConfig {
+            grain_radius: GRAIN_RADIUS,
+            damping: PARTICLE_DAMPING,
+            exhaust_interval: EXHAUST_TIMEOUT,
+            exhaust_lifetime: EXHAUST_LIVE_TIME,
+            gas_exhaust_interval: GAS_EXHAUST_TIMEOUT,
+            gas_exhaust_lifetime: GAS_EXHAUST_LIVE_TIME,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String, io::Error),
+    Parse(String, ron::Error),
+    Invalid(String, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "could not read {path}: {err}"),
+            ConfigError::Parse(path, err) => write!(f, "{path}: {err}"),
+            ConfigError::Invalid(path, reason) => write!(f, "{path}: {reason}"),
+        }
+    }
+}
+
+impl GameConfig {
+    /**
+     * Loads the config at path. Without a path the default config is used if there is one,
+     * otherwise the built in defaults.
+     */
+    pub fn load(path: Option<&str>) -> Result<GameConfig, ConfigError> {
+        let file = path.unwrap_or(DEFAULT_CONFIG_PATH);
+        let source = match fs::read_to_string(file) {
+            Ok(source) => source,
+            Err(_) if path.is_none() => return Ok(GameConfig::default()),
+            Err(err) => return Err(ConfigError::Read(file.to_string(), err)),
+        };
+
+        let config: GameConfig =
+            ron::from_str(&source).map_err(|err| ConfigError::Parse(file.to_string(), err))?;
+        config
+            .validate()
+            .map_err(|reason| ConfigError::Invalid(file.to_string(), reason))?;
+
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let positive = [
+            ("ship.speed_limit", self.ship.speed_limit),
+            ("ship.steering", self.ship.steering),
+            ("ship.stabilizer", self.ship.stabilizer),
+            ("ship.density", self.ship.density),
+            ("ship.cooling", self.ship.cooling),
+            ("asteroids.speed_limit", self.asteroids.speed_limit),
+            ("asteroids.density", self.asteroids.density),
+            ("particles.grain_radius", self.particles.grain_radius),
+            (
+                "particles.exhaust_interval",
+                self.particles.exhaust_interval,
+            ),
+            (
+                "particles.exhaust_lifetime",
+                self.particles.exhaust_lifetime,
+            ),
+            (
+                "particles.gas_exhaust_interval",
+                self.particles.gas_exhaust_interval,
+            ),
+            (
+                "particles.gas_exhaust_lifetime",
+                self.particles.gas_exhaust_lifetime,
+            ),
+        ];
+        for (name, value) in positive {
+            if value <= 0.0 {
+                return Err(format!("{name} must be greater than 0, got {value}"));
+            }
+        }
+
+        if self.weapons.is_empty() {
+            return Err("weapons must have at least one slot".to_string());
+        }
+        for (i, weapon) in self.weapons.iter().enumerate() {
+            weapon
+                .validate()
+                .map_err(|reason| format!("weapons[{i}].{reason}"))?;
+        }
+
+        for (name, value) in [
+            ("ship.damping", self.ship.damping),
+            ("particles.damping", self.particles.damping),
+        ] {
+            if value <= 0.0 || value > 1.0 {
+                return Err(format!("{name} must be in (0, 1], got {value}"));
+            }
+        }
+
+        for (name, size) in [
+            ("asteroids.large", AsteroidSize::Large),
+            ("asteroids.medium", AsteroidSize::Medium),
+            ("asteroids.small", AsteroidSize::Small),
+        ] {
+            let range = size.radius(&self.asteroids);
+            if range.is_empty() {
+                return Err(format!("{name} must have start <= end, got {range:?}"));
+            }
+            // the dents would reach through the center
+            let roughness = size.roughness();
+            if *range.start() <= roughness {
+                return Err(format!(
+                    "{name} must start above {roughness}, got {range:?}"
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct GameConfigLoader;
+
+impl AssetLoader for GameConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let config: GameConfig = ron::de::from_bytes(bytes)?;
+            config.validate().map_err(bevy::asset::Error::msg)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+/**
+ * The config file as watched by the asset server
+ */
+pub struct GameConfigHandle(pub Handle<GameConfig>);
+
+/**
+ * Provides the GameConfig resource. When the config file lives in the asset folder it is
+ * watched, and changes are applied to the running game.
+ */
+pub struct ConfigPlugin {
+    pub config: GameConfig,
+    pub path: Option<String>,
+}
+
+impl Plugin for ConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(self.config.clone())
+            .add_asset::<GameConfig>()
+            .init_asset_loader::<GameConfigLoader>()
+            .add_system(config_reload_system);
+
+        let file = self.path.as_deref().unwrap_or(DEFAULT_CONFIG_PATH);
+        if fs::metadata(file).is_err() {
+            // running on the built in defaults
+            return;
+        }
+
+        let asset_folder = app
+            .world
+            .get_resource::<AssetServerSettings>()
+            .map(|settings| settings.asset_folder.clone())
+            .unwrap_or_else(|| AssetServerSettings::default().asset_folder);
+        match asset_path(file, Path::new(&asset_folder)) {
+            Some(path) => {
+                let handle = app.world.resource::<AssetServer>().load(path);
+                app.insert_resource(GameConfigHandle(handle));
+            }
+            None => warn!("{file} is outside the asset folder, changes to it will not be reloaded"),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn asset_path(file: &str, asset_folder: &Path) -> Option<PathBuf> {
+    let root = bevy::asset::FileAssetIo::get_base_path().join(asset_folder);
+    let file = fs::canonicalize(file).ok()?;
+    let root = fs::canonicalize(root).ok()?;
+
+    file.strip_prefix(root).ok().map(Path::to_path_buf)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn asset_path(_file: &str, _asset_folder: &Path) -> Option<PathBuf> {
+    None
+}
+
+/**
+ * Swaps in the reloaded config and patches the handling components of the live ship and
+ * asteroids. Everything else reads the config when it spawns.
+ */
+#[allow(clippy::type_complexity)]
+pub fn config_reload_system(
+    mut ev_asset: EventReader<AssetEvent<GameConfig>>,
+    assets: Res<Assets<GameConfig>>,
+    watched: Option<Res<GameConfigHandle>>,
+    mut config: ResMut<GameConfig>,
+    mut ships: Query<
+        (
+            &mut SpeedLimit,
+            &mut Damping,
+            Option<&mut Mass>,
+            Option<&mut Heat>,
+        ),
+        With<Ship>,
+    >,
+    mut controls: Query<(
+        &mut Drive,
+        &mut SideThrusters,
+        &mut Loadout,
+        &mut SteeringControl,
+        &mut Stabilizer,
+    )>,
+    mut asteroids: Query<(&mut SpeedLimit, &mut Mass, &Points), (With<Asteroid>, Without<Ship>)>,
+) {
+    let watched = match watched {
+        Some(watched) => watched,
+        None => return,
+    };
+
+    for event in ev_asset.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if *handle != watched.0 {
+            continue;
+        }
+        let reloaded = match assets.get(handle) {
+            Some(reloaded) => reloaded,
+            None => continue,
+        };
+
+        *config = reloaded.clone();
+        info!("game config reloaded");
+
+        for (mut speed_limit, mut damping, mass, heat) in ships.iter_mut() {
+            speed_limit.0 = config.ship.speed_limit;
+            damping.0 = config.ship.damping;
+            // only there while the ship is alive
+            if let Some(mut mass) = mass {
+                *mass = Mass::disc(PLAYER_SIZE / 2.0, config.ship.density);
+            }
+            if let Some(mut heat) = heat {
+                heat.cooling = config.ship.cooling;
+            }
+        }
+        for (mut drive, mut thrusters, mut loadout, mut steering, mut stabilizer) in
+            controls.iter_mut()
+        {
+            drive.propulsion_force = config.ship.propulsion_force;
+            drive.reverse_force = config.ship.reverse_force;
+            thrusters.force = config.ship.side_thruster_force;
+            loadout.reload(config.weapons.clone());
+            *steering = SteeringControl::from(Angle::degrees(config.ship.steering));
+            stabilizer.0 = config.ship.stabilizer.to_radians();
+        }
+        for (mut speed_limit, mut mass, points) in asteroids.iter_mut() {
+            speed_limit.0 = config.asteroids.speed_limit;
+            *mass = Mass::polygon(points, config.asteroids.density);
+        }
+    }
+}
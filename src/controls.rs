@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+use crate::state::AppState;
+
+/**
+ * What the player is asking the ship to do, sampled from the keyboard once per frame and read
+ * by the control systems on every simulation step
+ */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ControlInput {
+    pub steer_left: bool,
+    pub steer_right: bool,
+    pub propulsion: bool,
+    pub reverse: bool,
+    pub thrust_left: bool,
+    pub thrust_right: bool,
+    // held until a simulation step fires it, so a press is neither lost nor fired twice
+    pub fire: bool,
+    // latched like fire
+    pub cycle_weapon: bool,
+    // the trigger is down, keeps firing as fast as the weapon allows
+    pub fire_held: bool,
+}
+
+impl ControlInput {
+    /**
+     * Packs the controls into one bit each, in field order
+     */
+    pub fn bits(&self) -> u16 {
+        [
+            self.steer_left,
+            self.steer_right,
+            self.propulsion,
+            self.reverse,
+            self.thrust_left,
+            self.thrust_right,
+            self.fire,
+            self.cycle_weapon,
+            self.fire_held,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (i, on)| bits | (*on as u16) << i)
+    }
+
+    pub fn from_bits(bits: u16) -> Self {
+        let on = |i: u16| bits & (1 << i) != 0;
+        ControlInput {
+            steer_left: on(0),
+            steer_right: on(1),
+            propulsion: on(2),
+            reverse: on(3),
+            thrust_left: on(4),
+            thrust_right: on(5),
+            fire: on(6),
+            cycle_weapon: on(7),
+            fire_held: on(8),
+        }
+    }
+}
+
+pub fn control_input_system(
+    keyboard: Res<Input<KeyCode>>,
+    state: Res<State<AppState>>,
+    mut input: ResMut<ControlInput>,
+) {
+    if *state.current() != AppState::Playing {
+        *input = ControlInput::default();
+        return;
+    }
+
+    input.steer_left = keyboard.any_pressed([KeyCode::Left, KeyCode::A]);
+    input.steer_right = keyboard.any_pressed([KeyCode::Right, KeyCode::D]);
+    input.propulsion = keyboard.any_pressed([KeyCode::Up, KeyCode::W]);
+    input.reverse = keyboard.any_pressed([KeyCode::Down, KeyCode::S]);
+    input.thrust_left = keyboard.pressed(KeyCode::Q);
+    input.thrust_right = keyboard.pressed(KeyCode::E);
+    input.fire |= keyboard.just_pressed(KeyCode::Space);
+    input.fire_held = keyboard.pressed(KeyCode::Space);
+    input.cycle_weapon |= keyboard.just_pressed(KeyCode::Tab);
+}
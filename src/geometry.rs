@@ -0,0 +1,439 @@
+use bevy::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+    // world position where the bodies touch
+    pub point: Vec2,
+    // unit vector pointing from the first body towards the second
+    pub normal: Vec2,
+    // how far the bodies overlap along the normal
+    pub depth: f32,
+}
+
+impl Contact {
+    /**
+     * The same contact seen from the second body
+     */
+    pub fn flipped(self) -> Self {
+        Contact {
+            normal: -self.normal,
+            ..self
+        }
+    }
+}
+
+/**
+ * Moves a polygon from entity local space into world space
+ */
+pub fn world_polygon(points: &[Vec2], transform: &Transform) -> Vec<Vec2> {
+    let offset = transform.translation.truncate();
+    points
+        .iter()
+        .map(|p| (transform.rotation * p.extend(0.0)).truncate() + offset)
+        .collect()
+}
+
+pub fn polygon_center(polygon: &[Vec2]) -> Vec2 {
+    polygon.iter().fold(Vec2::ZERO, |sum, p| sum + *p) / polygon.len().max(1) as f32
+}
+
+/**
+ * Even-odd rule, so it also holds for the concave asteroid outlines
+ */
+pub fn point_in_polygon(point: Vec2, polygon: &[Vec2]) -> bool {
+    let mut inside = false;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < (b.x - a.x) * (point.y - a.y) / (b.y - a.y) + a.x
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+pub fn closest_point_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let len_sq = ab.length_squared();
+    if len_sq < f32::EPSILON {
+        return a;
+    }
+
+    let t = ((point - a).dot(ab) / len_sq).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+pub fn closest_point_on_polygon(point: Vec2, polygon: &[Vec2]) -> Vec2 {
+    let mut closest = polygon[0];
+    let mut closest_dist = f32::MAX;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let candidate = closest_point_on_segment(point, *a, b);
+        let dist = candidate.distance_squared(point);
+        if dist < closest_dist {
+            closest = candidate;
+            closest_dist = dist;
+        }
+    }
+
+    closest
+}
+
+pub fn segment_intersection(a1: Vec2, a2: Vec2, b1: Vec2, b2: Vec2) -> Option<Vec2> {
+    let r = a2 - a1;
+    let s = b2 - b1;
+    let denom = r.perp_dot(s);
+    if denom.abs() < f32::EPSILON {
+        // parallel
+        return None;
+    }
+
+    let t = (b1 - a1).perp_dot(s) / denom;
+    let u = (b1 - a1).perp_dot(r) / denom;
+    if (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u) {
+        Some(a1 + r * t)
+    } else {
+        None
+    }
+}
+
+/**
+ * The crossing of the segment from a to b with the polygon outline closest to a
+ */
+pub fn segment_polygon_intersection(a: Vec2, b: Vec2, polygon: &[Vec2]) -> Option<Vec2> {
+    polygon
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| segment_intersection(a, b, *p, polygon[(i + 1) % polygon.len()]))
+        .min_by(|x, y| x.distance_squared(a).total_cmp(&y.distance_squared(a)))
+}
+
+pub fn circle_circle_contact(a: Vec2, ar: f32, b: Vec2, br: f32) -> Option<Contact> {
+    let distance = a.distance(b);
+    if distance >= ar + br {
+        return None;
+    }
+
+    let normal = (b - a).try_normalize().unwrap_or(Vec2::X);
+    let depth = ar + br - distance;
+    Some(Contact {
+        point: a + normal * (ar - depth / 2.0),
+        normal,
+        depth,
+    })
+}
+
+pub fn circle_polygon_contact(center: Vec2, radius: f32, polygon: &[Vec2]) -> Option<Contact> {
+    let closest = closest_point_on_polygon(center, polygon);
+    let offset = closest - center;
+    let distance = offset.length();
+    let inside = point_in_polygon(center, polygon);
+    if !inside && distance >= radius {
+        return None;
+    }
+
+    let towards_surface = offset
+        .try_normalize()
+        .unwrap_or_else(|| (polygon_center(polygon) - center).normalize_or_zero());
+    if inside {
+        // the circle has to travel out through the closest edge
+        Some(Contact {
+            point: closest,
+            normal: -towards_surface,
+            depth: radius + distance,
+        })
+    } else {
+        Some(Contact {
+            point: closest,
+            normal: towards_surface,
+            depth: radius - distance,
+        })
+    }
+}
+
+/**
+ * Vertices of either polygon found inside the other make up the contact. Polygons that only
+ * cross edges, without a vertex inside, fall back to the edge intersections, pushed apart
+ * along the line between their centers.
+ */
+pub fn polygon_polygon_contact(a: &[Vec2], b: &[Vec2]) -> Option<Contact> {
+    let mut points = Vec::new();
+    let mut normal = Vec2::ZERO;
+    let mut depth: f32 = 0.0;
+
+    for &p in a.iter().filter(|p| point_in_polygon(**p, b)) {
+        let closest = closest_point_on_polygon(p, b);
+        normal += p - closest;
+        depth = depth.max(p.distance(closest));
+        points.push(p);
+    }
+    for &p in b.iter().filter(|p| point_in_polygon(**p, a)) {
+        let closest = closest_point_on_polygon(p, a);
+        normal += closest - p;
+        depth = depth.max(p.distance(closest));
+        points.push(p);
+    }
+
+    if points.is_empty() {
+        for (i, a1) in a.iter().enumerate() {
+            let a2 = a[(i + 1) % a.len()];
+            for (j, b1) in b.iter().enumerate() {
+                let b2 = b[(j + 1) % b.len()];
+                if let Some(p) = segment_intersection(*a1, a2, *b1, b2) {
+                    points.push(p);
+                }
+            }
+        }
+    }
+
+    if points.is_empty() {
+        return None;
+    }
+
+    let centers = polygon_center(b) - polygon_center(a);
+    let normal = normal
+        .try_normalize()
+        .or_else(|| centers.try_normalize())
+        .unwrap_or(Vec2::X);
+    if depth == 0.0 {
+        // how far the outlines overlap along the normal
+        let reach = a.iter().map(|p| p.dot(normal)).fold(f32::MIN, f32::max);
+        let start = b.iter().map(|p| p.dot(normal)).fold(f32::MAX, f32::min);
+        depth = (reach - start).max(0.0);
+    }
+    Some(Contact {
+        point: polygon_center(&points),
+        normal,
+        depth,
+    })
+}
+
+pub fn polygon_area(polygon: &[Vec2]) -> f32 {
+    let mut twice_area = 0.0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        twice_area += a.perp_dot(b);
+    }
+
+    (twice_area / 2.0).abs()
+}
+
+/**
+ * Center of mass of the polygon's area, as opposed to the average of its vertices
+ */
+pub fn polygon_centroid(polygon: &[Vec2]) -> Vec2 {
+    let mut twice_area = 0.0;
+    let mut centroid = Vec2::ZERO;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let cross = a.perp_dot(b);
+        twice_area += cross;
+        centroid += (*a + b) * cross;
+    }
+
+    if twice_area.abs() < f32::EPSILON {
+        // degenerate, all points on a line
+        return polygon_center(polygon);
+    }
+    centroid / (3.0 * twice_area)
+}
+
+/**
+ * Largest distance from the point to any vertex
+ */
+pub fn polygon_radius(polygon: &[Vec2], center: Vec2) -> f32 {
+    polygon
+        .iter()
+        .map(|p| p.distance(center))
+        .fold(0.0, f32::max)
+}
+
+/**
+ * Keeps the part of the polygon on the left of the line through origin along direction
+ * (Sutherland-Hodgman with a single clip edge)
+ */
+pub fn clip_polygon(polygon: &[Vec2], origin: Vec2, direction: Vec2) -> Vec<Vec2> {
+    let side = |p: Vec2| direction.perp_dot(p - origin);
+    let mut clipped = Vec::new();
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let (sa, sb) = (side(*a), side(b));
+        if sa >= 0.0 {
+            clipped.push(*a);
+        }
+        if (sa >= 0.0) != (sb >= 0.0) {
+            clipped.push(*a + (b - *a) * (sa / (sa - sb)));
+        }
+    }
+
+    clipped
+}
+
+pub fn split_polygon(polygon: &[Vec2], origin: Vec2, direction: Vec2) -> (Vec<Vec2>, Vec<Vec2>) {
+    (
+        clip_polygon(polygon, origin, direction),
+        clip_polygon(polygon, origin, -direction),
+    )
+}
+
+// pieces smaller than this share of what was cracked count as a miss
+const MIN_FRAGMENT_SHARE: f32 = 0.05;
+
+/**
+ * Cracks the polygon into up to the given number of pieces. The first crack follows the line
+ * of impact, every following one runs across the previous crack through the biggest piece.
+ */
+pub fn fracture(polygon: &[Vec2], impact: Vec2, direction: Vec2, pieces: usize) -> Vec<Vec<Vec2>> {
+    let mut fragments = vec![polygon.to_vec()];
+    let mut direction = direction.try_normalize().unwrap_or(Vec2::X);
+    let mut origin = impact;
+
+    while fragments.len() < pieces {
+        let biggest = (0..fragments.len())
+            .max_by(|a, b| polygon_area(&fragments[*a]).total_cmp(&polygon_area(&fragments[*b])))
+            .unwrap_or(0);
+        let piece = fragments.swap_remove(biggest);
+        let area = polygon_area(&piece);
+        let is_sliver =
+            |part: &[Vec2]| part.len() < 3 || polygon_area(part) < area * MIN_FRAGMENT_SHARE;
+
+        let (mut left, mut right) = split_polygon(&piece, origin, direction);
+        if is_sliver(&left) || is_sliver(&right) {
+            // the line only grazed the piece, crack it through the middle instead
+            (left, right) = split_polygon(&piece, polygon_centroid(&piece), direction);
+        }
+        if is_sliver(&left) || is_sliver(&right) {
+            fragments.push(piece);
+            break;
+        }
+
+        fragments.push(left);
+        fragments.push(right);
+        direction = direction.perp();
+        origin = fragments
+            .iter()
+            .max_by(|a, b| polygon_area(a).total_cmp(&polygon_area(b)))
+            .map(|biggest| polygon_centroid(biggest))
+            .unwrap_or(origin);
+    }
+
+    fragments
+}
+
+/**
+ * Second moment of area around the local origin. Times the density it is the moment of inertia
+ * of a body spinning around that origin.
+ */
+pub fn polygon_inertia(polygon: &[Vec2]) -> f32 {
+    let mut sum = 0.0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        sum += a.perp_dot(b) * (a.dot(*a) + a.dot(b) + b.dot(b));
+    }
+
+    (sum / 12.0).abs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::vec2;
+
+    fn rectangle(width: f32, height: f32) -> Vec<Vec2> {
+        let (x, y) = (width / 2.0, height / 2.0);
+        vec![vec2(-x, -y), vec2(x, -y), vec2(x, y), vec2(-x, y)]
+    }
+
+    #[test]
+    fn point_in_polygon_follows_the_outline() {
+        // an L, the notch at the top right is outside
+        let l = [
+            vec2(0.0, 0.0),
+            vec2(2.0, 0.0),
+            vec2(2.0, 1.0),
+            vec2(1.0, 1.0),
+            vec2(1.0, 2.0),
+            vec2(0.0, 2.0),
+        ];
+        for (point, inside) in [
+            (vec2(0.5, 0.5), true),
+            (vec2(1.5, 0.5), true),
+            (vec2(0.5, 1.5), true),
+            (vec2(1.5, 1.5), false),
+            (vec2(-0.5, 0.5), false),
+            (vec2(0.5, 2.5), false),
+        ] {
+            assert_eq!(point_in_polygon(point, &l), inside, "{point}");
+        }
+    }
+
+    #[test]
+    fn polygon_polygon_contact_reports_overlap_and_depth() {
+        // a small square poking into a big one from the side
+        let big = rectangle(2.0, 2.0);
+        for (offset, touching) in [
+            (vec2(1.2, 0.0), true),
+            (vec2(0.0, -1.3), true),
+            (vec2(-1.2, 0.3), true),
+            (vec2(2.0, 0.0), false),
+            (vec2(1.4, 1.6), false),
+        ] {
+            let small: Vec<Vec2> = rectangle(1.0, 1.0).iter().map(|p| *p + offset).collect();
+            let contact = polygon_polygon_contact(&big, &small);
+            assert_eq!(contact.is_some(), touching, "offset {offset}");
+            if let Some(contact) = contact {
+                assert!(contact.depth > 0.0, "offset {offset}, {contact:?}");
+                assert!(
+                    contact.normal.dot(offset) > 0.0,
+                    "offset {offset}, {contact:?}"
+                );
+            }
+        }
+
+        // a cross, the edges meet without a corner inside
+        let wide = rectangle(20.0, 4.0);
+        let tall: Vec<Vec2> = rectangle(4.0, 20.0)
+            .iter()
+            .map(|p| *p + vec2(3.0, 0.0))
+            .collect();
+        let contact = polygon_polygon_contact(&wide, &tall).unwrap();
+        assert_eq!(contact.normal, Vec2::X);
+        assert!((contact.depth - 9.0).abs() < 1e-4, "{contact:?}");
+    }
+
+    #[test]
+    fn fracture_keeps_the_area_of_the_polygon() {
+        let polygon = rectangle(4.0, 2.0);
+        for pieces in 1..=4 {
+            let fragments = fracture(&polygon, vec2(0.5, 0.0), Vec2::Y, pieces);
+            assert_eq!(fragments.len(), pieces);
+            let area: f32 = fragments.iter().map(|f| polygon_area(f)).sum();
+            assert!((area - 8.0).abs() < 1e-4, "{pieces} pieces, area {area}");
+        }
+
+        // a crack grazing a corner goes through the middle instead
+        let fragments = fracture(&polygon, vec2(1.99, 0.0), Vec2::Y, 2);
+        assert!(fragments.iter().all(|f| polygon_area(f) > 8.0 * 0.05));
+    }
+
+    #[test]
+    fn polygon_inertia_matches_rectangles() {
+        for (width, height) in [(2.0, 2.0), (2.0, 4.0), (10.0, 1.0)] {
+            let expected = width * height * (width * width + height * height) / 12.0;
+            let inertia = polygon_inertia(&rectangle(width, height));
+            assert!(
+                (inertia - expected).abs() < expected * 1e-5,
+                "{width}x{height}: {inertia}, expected {expected}"
+            );
+        }
+        // the winding doesn't matter
+        let mut reversed = rectangle(2.0, 4.0);
+        reversed.reverse();
+        assert_eq!(
+            polygon_inertia(&reversed),
+            polygon_inertia(&rectangle(2.0, 4.0))
+        );
+    }
+}
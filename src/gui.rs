@@ -6,7 +6,13 @@ use bevy::{
 };
 
 use crate::{
-    random::Random, FRAME_X_OFFSET, GAME_FRAME_HEIGHT, GAME_FRAME_WIDTH, LIGHT, SCREEN_HEIGHT,
+    hull::{Hull, Shield},
+    lives::Lives,
+    random::{GameUi, Random},
+    score::Score,
+    wave::CurrentStage,
+    weapons::{Heat, Loadout},
+    Ship, ESCURO, FRAME_X_OFFSET, GAME_FRAME_HEIGHT, GAME_FRAME_WIDTH, LIGHT, SCREEN_HEIGHT,
     SCREEN_WIDTH,
 };
 
@@ -16,6 +22,34 @@ const STAT_FRAME_WIDTH: f32 = 244.0;
 const STAT_FRAME_HEIGHT: f32 = 512.0;
 const GAME_FRAME_SIZE: Vec2 = Vec2::from_array([GAME_FRAME_WIDTH, GAME_FRAME_HEIGHT]);
 const STAT_FRAME_SIZE: Vec2 = Vec2::from_array([STAT_FRAME_WIDTH, STAT_FRAME_HEIGHT]);
+// under the weapon name, the left edge is where an empty gauge starts filling up
+const HEAT_BAR_LEFT: f32 = SCREEN_WIDTH / 2.0 - 25.0 * 4.0;
+const HEAT_BAR_Y: f32 = 20.0 * 4.0;
+const HEAT_BAR_SIZE: Vec2 = Vec2::from_array([21.5 * 4.0, 1.5 * 4.0]);
+
+/**
+ * The camera, the frames around the playing field and the stats drawn in them
+ */
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(setup_system)
+            .add_startup_system(setup_game_ui)
+            .add_system(lives_hud_system)
+            .add_system(score_hud_system)
+            .add_system(hull_hud_system)
+            .add_system(stage_hud_system)
+            .add_system(weapon_hud_system)
+            .add_system(heat_hud_system);
+    }
+}
+
+fn setup_system(mut commands: Commands) {
+    commands.spawn_bundle(Camera2dBundle::default());
+}
+
+pub struct FontAtlas(pub Handle<TextureAtlas>);
 
 pub fn draw_text(
     commands: &mut Commands,
@@ -25,73 +59,50 @@ pub fn draw_text(
     y: f32,
     scale: f32,
     letter_spacing: f32,
-) {
+) -> Vec<Entity> {
+    let mut letters = Vec::new();
     for (i, c) in text.chars().enumerate() {
-        match c {
-            'A'..='P' => {
-                commands.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlas_handle.clone(),
-                    sprite: TextureAtlasSprite {
-                        color: LIGHT,
-                        index: (c as u8 - 49) as usize,
-                        custom_size: Some(vec2(8.0 * scale, 8.0 * scale)),
-                        ..default()
-                    },
-                    transform: Transform {
-                        translation: vec3(x + (i as f32 * letter_spacing) * scale, y, 2.0),
-                        ..default()
-                    },
-                    ..default()
-                });
-            }
-            'Q'..='Z' => {
-                commands.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlas_handle.clone(),
-                    sprite: TextureAtlasSprite {
-                        color: LIGHT,
-                        index: (c as u8 - 43) as usize,
-                        custom_size: Some(vec2(8.0 * scale, 8.0 * scale)),
-                        ..default()
-                    },
-                    transform: Transform {
-                        translation: vec3(x + (i as f32 * letter_spacing) * scale, y, 2.0),
-                        ..default()
-                    },
+        let index = match c {
+            'A'..='P' => c as u8 - 49,
+            'Q'..='Z' => c as u8 - 43,
+            '0'..='9' => c as u8 - 48,
+            _ => continue,
+        } as usize;
+
+        let letter = commands
+            .spawn_bundle(SpriteSheetBundle {
+                texture_atlas: atlas_handle.clone(),
+                sprite: TextureAtlasSprite {
+                    color: LIGHT,
+                    index,
+                    custom_size: Some(vec2(8.0 * scale, 8.0 * scale)),
                     ..default()
-                });
-            }
-            '0'..='9' => {
-                commands.spawn_bundle(SpriteSheetBundle {
-                    texture_atlas: atlas_handle.clone(),
-                    sprite: TextureAtlasSprite {
-                        color: LIGHT,
-                        index: (c as u8 - 48) as usize,
-                        custom_size: Some(vec2(8.0 * scale, 8.0 * scale)),
-                        ..default()
-                    },
-                    transform: Transform {
-                        translation: vec3(x + (i as f32 * letter_spacing) * scale, y, 2.0),
-                        ..default()
-                    },
+                },
+                transform: Transform {
+                    translation: vec3(x + (i as f32 * letter_spacing) * scale, y, 2.0),
                     ..default()
-                });
-            }
-            _ => {}
-        }
+                },
+                ..default()
+            })
+            .id();
+        letters.push(letter);
     }
+
+    letters
 }
 
 pub fn setup_game_ui(
     mut commands: Commands,
     window: Res<WindowDescriptor>,
     asset_server: Res<AssetServer>,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<GameUi>>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
     let radar_handle = asset_server.load("radar.png");
     let atlas_handle = asset_server.load("atlas.png");
     let atlas = TextureAtlas::from_grid(atlas_handle, vec2(8.0, 8.0), 16, 10);
     let texture_atlas_handle = texture_atlases.add(atlas);
+    commands.insert_resource(FontAtlas(texture_atlas_handle.clone()));
 
     commands.spawn_bundle(SpriteBundle {
         texture: asset_server.load("game_frame.png"),
@@ -180,15 +191,6 @@ pub fn setup_game_ui(
         },
         ..default()
     });
-    draw_text(
-        &mut commands,
-        &texture_atlas_handle,
-        "100",
-        SCREEN_WIDTH / 2.0 - (51.5 * 4.0),
-        -8.0 * 4.0,
-        4.0,
-        9.0,
-    );
     draw_text(
         &mut commands,
         &texture_atlas_handle,
@@ -211,15 +213,6 @@ pub fn setup_game_ui(
         },
         ..default()
     });
-    draw_text(
-        &mut commands,
-        &texture_atlas_handle,
-        "100",
-        SCREEN_WIDTH / 2.0 - (51.5 * 4.0),
-        12.0 * 4.0,
-        4.0,
-        9.0,
-    );
     draw_text(
         &mut commands,
         &texture_atlas_handle,
@@ -238,15 +231,6 @@ pub fn setup_game_ui(
         4.0,
         8.0,
     );
-    draw_text(
-        &mut commands,
-        &texture_atlas_handle,
-        "000000",
-        SCREEN_WIDTH / 2.0 - STAT_FRAME_SIZE.x + 10.0 * 4.0,
-        SCREEN_HEIGHT / 2.0 - 30.5 * 4.0,
-        4.0,
-        8.0,
-    );
     commands.spawn_bundle(SpriteBundle {
         texture: asset_server.load("points_frame.png"),
         sprite: Sprite {
@@ -264,15 +248,197 @@ pub fn setup_game_ui(
         },
         ..default()
     });
+    // the heat gauge, empty until the ship fires
+    commands
+        .spawn_bundle(SpriteBundle {
+            sprite: Sprite {
+                color: LIGHT,
+                custom_size: Some(vec2(0.0, HEAT_BAR_SIZE.y)),
+                ..default()
+            },
+            transform: Transform {
+                translation: vec3(HEAT_BAR_LEFT, HEAT_BAR_Y, 2.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(HeatBar);
+}
 
-    // Lives
-    draw_text(
-        &mut commands,
-        &texture_atlas_handle,
-        "3",
-        SCREEN_WIDTH / 2.0 - (16.5 * 4.0),
-        -7.5 * 4.0,
-        4.0,
-        9.0,
-    );
+#[derive(Debug, Component)]
+pub struct HeatBar;
+
+/**
+ * HUD readouts that are redrawn whenever the value behind them changes
+ */
+#[derive(Debug, Component, Clone, Copy, PartialEq, Eq)]
+pub enum HudField {
+    Lives,
+    Points,
+    Hull,
+    Shield,
+    Stage,
+    Weapon,
+}
+
+impl HudField {
+    // (x, y, scale, letter spacing)
+    fn layout(&self) -> (f32, f32, f32, f32) {
+        match self {
+            HudField::Lives => (SCREEN_WIDTH / 2.0 - (16.5 * 4.0), -7.5 * 4.0, 4.0, 9.0),
+            HudField::Hull => (SCREEN_WIDTH / 2.0 - (51.5 * 4.0), -8.0 * 4.0, 4.0, 9.0),
+            HudField::Shield => (SCREEN_WIDTH / 2.0 - (51.5 * 4.0), 12.0 * 4.0, 4.0, 9.0),
+            HudField::Points => (
+                SCREEN_WIDTH / 2.0 - STAT_FRAME_SIZE.x + 10.0 * 4.0,
+                SCREEN_HEIGHT / 2.0 - 30.5 * 4.0,
+                4.0,
+                8.0,
+            ),
+            // smaller than the rest to fit two digits next to the STAGE label
+            HudField::Stage => (
+                SCREEN_WIDTH / 2.0 - STAT_FRAME_SIZE.x + 8.0 * 4.0 + 5.5 * 8.0 * 4.0,
+                SCREEN_HEIGHT / 2.0 - 10.0 * 4.0,
+                3.0,
+                7.0,
+            ),
+            // above the lives frame, right of the SHLD label
+            HudField::Weapon => (SCREEN_WIDTH / 2.0 - 23.0 * 4.0, 25.0 * 4.0, 2.0, 7.0),
+        }
+    }
+}
+
+pub fn redraw_hud_field(
+    commands: &mut Commands,
+    atlas: &FontAtlas,
+    fields: &Query<(Entity, &HudField)>,
+    field: HudField,
+    text: &str,
+) {
+    for (entity, _) in fields.iter().filter(|(_, f)| **f == field) {
+        commands.entity(entity).despawn();
+    }
+
+    let (x, y, scale, letter_spacing) = field.layout();
+    for letter in draw_text(commands, &atlas.0, text, x, y, scale, letter_spacing) {
+        commands.entity(letter).insert(field);
+    }
+}
+
+pub fn lives_hud_system(
+    mut commands: Commands,
+    atlas: Res<FontAtlas>,
+    lives: Res<Lives>,
+    fields: Query<(Entity, &HudField)>,
+) {
+    if lives.is_changed() {
+        redraw_hud_field(
+            &mut commands,
+            &atlas,
+            &fields,
+            HudField::Lives,
+            &lives.remaining.to_string(),
+        );
+    }
+}
+
+pub fn score_hud_system(
+    mut commands: Commands,
+    atlas: Res<FontAtlas>,
+    score: Res<Score>,
+    fields: Query<(Entity, &HudField)>,
+) {
+    if score.is_changed() {
+        redraw_hud_field(
+            &mut commands,
+            &atlas,
+            &fields,
+            HudField::Points,
+            &format!("{:06}", score.0),
+        );
+    }
+}
+
+pub fn hull_hud_system(
+    mut commands: Commands,
+    atlas: Res<FontAtlas>,
+    hulls: Query<&Hull, (With<Ship>, Changed<Hull>)>,
+    shields: Query<&Shield, (With<Ship>, Changed<Shield>)>,
+    fields: Query<(Entity, &HudField)>,
+    mut shown_shield: Local<Option<u32>>,
+) {
+    for hull in hulls.iter() {
+        redraw_hud_field(
+            &mut commands,
+            &atlas,
+            &fields,
+            HudField::Hull,
+            &format!("{:>3}", hull.percent()),
+        );
+    }
+    // the regen timer changes the shield on every step, only a new percentage is redrawn
+    for shield in shields.iter() {
+        let percent = shield.percent();
+        if *shown_shield != Some(percent) {
+            redraw_hud_field(
+                &mut commands,
+                &atlas,
+                &fields,
+                HudField::Shield,
+                &format!("{:>3}", percent),
+            );
+            *shown_shield = Some(percent);
+        }
+    }
+}
+
+pub fn stage_hud_system(
+    mut commands: Commands,
+    atlas: Res<FontAtlas>,
+    stage: Res<CurrentStage>,
+    fields: Query<(Entity, &HudField)>,
+) {
+    if stage.is_changed() {
+        redraw_hud_field(
+            &mut commands,
+            &atlas,
+            &fields,
+            HudField::Stage,
+            &format!("{:02}", stage.0.min(99)),
+        );
+    }
+}
+
+pub fn weapon_hud_system(
+    mut commands: Commands,
+    atlas: Res<FontAtlas>,
+    loadouts: Query<&Loadout, (With<Ship>, Changed<Loadout>)>,
+    fields: Query<(Entity, &HudField)>,
+    mut shown: Local<String>,
+) {
+    // the loadout also changes with every shot, only a new name is redrawn
+    for loadout in loadouts.iter() {
+        let name = &loadout.weapon().name;
+        if *name != *shown {
+            redraw_hud_field(&mut commands, &atlas, &fields, HudField::Weapon, name);
+            *shown = name.clone();
+        }
+    }
+}
+
+pub fn heat_hud_system(
+    heats: Query<&Heat, (With<Ship>, Changed<Heat>)>,
+    mut bars: Query<(&mut Sprite, &mut Transform), With<HeatBar>>,
+) {
+    for heat in heats.iter() {
+        let width = HEAT_BAR_SIZE.x * heat.level;
+        for (mut sprite, mut transform) in bars.iter_mut() {
+            sprite.custom_size = Some(vec2(width, HEAT_BAR_SIZE.y));
+            // dimmed while the weapons are locked
+            sprite.color = match heat.overheated {
+                true => ESCURO,
+                false => LIGHT,
+            };
+            transform.translation.x = HEAT_BAR_LEFT + width / 2.0;
+        }
+    }
 }
@@ -0,0 +1,108 @@
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::{
+    asteroid::Asteroid, random::Seed, replay::ReplayRecorder, score::Score, state::AppState,
+    timestep::SimulationClock, wave::CurrentStage, weapons::Bullet, PlayerDeathEvent, TimedRemoval,
+};
+
+/**
+ * Times the ship was destroyed this game
+ */
+#[derive(Debug, Default)]
+pub struct Deaths(pub u32);
+
+pub fn reset_deaths_system(mut deaths: ResMut<Deaths>) {
+    *deaths = Deaths::default();
+}
+
+pub fn death_count_system(mut ev_death: EventReader<PlayerDeathEvent>, mut deaths: ResMut<Deaths>) {
+    deaths.0 += ev_death.iter().count() as u32;
+}
+
+/**
+ * Where a headless run ended up
+ */
+#[derive(Debug)]
+pub struct HeadlessSummary {
+    pub ticks: u64,
+    pub seed: u64,
+    pub game_over: bool,
+    pub score: u32,
+    pub stage: u32,
+    pub deaths: u32,
+    pub asteroids: usize,
+    pub bullets: usize,
+    pub particles: usize,
+    pub entities: u32,
+}
+
+impl HeadlessSummary {
+    fn collect(world: &mut World) -> Self {
+        let mut asteroids = world.query_filtered::<(), With<Asteroid>>();
+        let mut bullets = world.query_filtered::<(), With<Bullet>>();
+        let mut particles = world.query_filtered::<(), With<TimedRemoval>>();
+        HeadlessSummary {
+            ticks: world.resource::<SimulationClock>().tick,
+            seed: world.resource::<Seed>().value(),
+            game_over: *world.resource::<State<AppState>>().current() == AppState::GameOver,
+            score: world.resource::<Score>().0,
+            stage: world.resource::<CurrentStage>().0,
+            deaths: world.resource::<Deaths>().0,
+            asteroids: asteroids.iter(world).count(),
+            bullets: bullets.iter(world).count(),
+            particles: particles.iter(world).count(),
+            entities: world.entities().len(),
+        }
+    }
+}
+
+impl fmt::Display for HeadlessSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let end = if self.game_over {
+            "game over"
+        } else {
+            "still playing"
+        };
+        writeln!(f, "{} ticks, {end}", self.ticks)?;
+        writeln!(f, "seed:      {}", self.seed)?;
+        writeln!(f, "score:     {}", self.score)?;
+        writeln!(f, "stage:     {}", self.stage)?;
+        writeln!(f, "deaths:    {}", self.deaths)?;
+        writeln!(f, "asteroids: {}", self.asteroids)?;
+        writeln!(f, "bullets:   {}", self.bullets)?;
+        writeln!(f, "particles: {}", self.particles)?;
+        write!(f, "entities:  {}", self.entities)
+    }
+}
+
+/**
+ * Steps the game as fast as it goes until it is over, or until max_ticks steps have been
+ * simulated. When the game is over the entity counts are from right before the last update,
+ * since leaving the game clears the field.
+ */
+pub fn run_headless(mut app: App, max_ticks: Option<u64>) -> HeadlessSummary {
+    loop {
+        let before = HeadlessSummary::collect(&mut app.world);
+        app.update();
+        let after = HeadlessSummary::collect(&mut app.world);
+
+        if after.game_over {
+            return HeadlessSummary {
+                asteroids: before.asteroids,
+                bullets: before.bullets,
+                particles: before.particles,
+                entities: before.entities,
+                ..after
+            };
+        }
+        if matches!(max_ticks, Some(max) if after.ticks >= max) {
+            // the game never leaves Playing here, so the recording isn't saved on exit
+            if let Some(recorder) = app.world.get_resource::<ReplayRecorder>() {
+                recorder.save();
+            }
+            return after;
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+
+pub const HULL_MAX: f32 = 100.0;
+pub const SHIELD_MAX: f32 = 100.0;
+// in seconds
+pub const SHIELD_REGEN_DELAY: f32 = 3.0;
+// per second
+pub const SHIELD_REGEN_RATE: f32 = 20.0;
+
+#[derive(Debug, Component)]
+pub struct Hull {
+    pub integrity: f32,
+    pub max: f32,
+}
+
+impl Hull {
+    pub fn new(max: f32) -> Self {
+        Hull {
+            integrity: max,
+            max,
+        }
+    }
+
+    pub fn percent(&self) -> u32 {
+        (self.integrity / self.max * 100.0).ceil() as u32
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct Shield {
+    pub strength: f32,
+    pub max: f32,
+    pub regen_delay: Timer,
+}
+
+impl Shield {
+    pub fn new(max: f32) -> Self {
+        Shield {
+            strength: max,
+            max,
+            regen_delay: Timer::from_seconds(SHIELD_REGEN_DELAY, false),
+        }
+    }
+
+    pub fn percent(&self) -> u32 {
+        (self.strength / self.max * 100.0).ceil() as u32
+    }
+
+    /**
+     * Soaks up as much of the damage as the shield has left and returns the rest
+     */
+    pub fn absorb(&mut self, damage: f32) -> f32 {
+        self.regen_delay.reset();
+        let absorbed = damage.min(self.strength);
+        self.strength -= absorbed;
+
+        damage - absorbed
+    }
+}
+
+pub fn shield_regen_system(time: Res<Time>, mut query: Query<&mut Shield>) {
+    for mut shield in query.iter_mut() {
+        if shield.strength >= shield.max {
+            continue;
+        }
+
+        shield.regen_delay.tick(time.delta());
+        if shield.regen_delay.finished() {
+            shield.strength =
+                (shield.strength + SHIELD_REGEN_RATE * time.delta_seconds()).min(shield.max);
+        }
+    }
+}
@@ -0,0 +1,541 @@
+use asteroid::*;
+use bevy::ecs::component::Component;
+use bevy::{
+    input::InputSystem,
+    math::{vec2, vec3},
+    prelude::*,
+};
+use bevy_prototype_lyon::entity::ShapeBundle;
+use bevy_prototype_lyon::prelude::{tess::math::Angle, *};
+use boundary::*;
+use collision::*;
+use config::{ConfigPlugin, GameConfig};
+use controls::{control_input_system, ControlInput};
+use derive_more::From;
+use gui::{FontAtlas, HudPlugin};
+use headless::{death_count_system, reset_deaths_system, Deaths};
+use hull::*;
+use lives::*;
+use movement::*;
+use particles::*;
+use rand::Rng;
+use random::{new_game_seed_system, Random, RandomPlugin, Stars};
+use replay::{
+    playback_input_system, record_input_system, rewind_replay_system, save_recording_system,
+    start_recording_system, Replay, ReplayPlayer, ReplayRecorder,
+};
+use score::*;
+use state::*;
+use std::time::Duration;
+use timestep::{
+    FixedTimestepStage, FixedUpdate, Interpolated, SimulationApp, SimulationClock, TIME_STEP,
+};
+use weapons::*;
+
+pub mod asteroid;
+pub mod boundary;
+pub mod collision;
+pub mod config;
+pub mod controls;
+pub mod geometry;
+pub mod gui;
+pub mod headless;
+pub mod hull;
+pub mod lives;
+pub mod movement;
+pub mod particles;
+pub mod random;
+pub mod replay;
+pub mod score;
+pub mod spatial;
+pub mod state;
+pub mod timestep;
+pub mod wave;
+pub mod weapons;
+
+pub const SCREEN_HEIGHT: f32 = 512.0;
+pub const SCREEN_WIDTH: f32 = 1024.0;
+pub const GAME_FRAME_WIDTH: f32 = 776.0;
+pub const GAME_FRAME_HEIGHT: f32 = 512.0;
+pub const GAME_BORDER_OFFSET: f32 = 8.0;
+pub const FRAME_X_OFFSET: f32 = (SCREEN_WIDTH - GAME_FRAME_WIDTH) / 2.0;
+pub const FRAME_START_Y: f32 = -(SCREEN_HEIGHT / 2.0) + 4.0;
+pub const FRAME_END_Y: f32 = SCREEN_HEIGHT / 2.0 - 4.0;
+pub const FRAME_START_X: f32 = -GAME_FRAME_WIDTH / 2.0 - FRAME_X_OFFSET;
+pub const FRAME_END_X: f32 = (GAME_FRAME_WIDTH / 2.0) - FRAME_X_OFFSET;
+
+pub const SCREEN: Vec2 = Vec2::from_array([SCREEN_WIDTH, SCREEN_HEIGHT]);
+// pub const PIXELS_PER_METER: f32 = 30.0 / SCALE;
+
+pub const PLAYER_SIZE: f32 = 20.0;
+pub const PLAYER_DAMPING: f32 = 0.992;
+pub const POLY_LINE_WIDTH: f32 = 1.0;
+
+pub const DARK: Color = Color::rgb(0.191, 0.184, 0.156);
+pub const ESCURO: Color = Color::rgb(0.382, 0.368, 0.312);
+pub const LIGHT: Color = Color::rgb(0.852, 0.844, 0.816);
+
+/**
+ * Labels shared by the gameplay systems of the different plugins, for ordering a step
+ */
+#[derive(Debug, Clone, PartialEq, Eq, Hash, SystemLabel)]
+pub enum GameSystem {
+    Collision,
+    Input,
+    Movement,
+    Boundary,
+    Particles,
+    Despawning,
+}
+
+pub struct Debug(pub bool);
+
+/**
+ * How the game is set up when it is added to an app
+ */
+#[derive(Debug, Default, Clone)]
+pub struct GameSettings {
+    pub config: GameConfig,
+    // the file config was loaded from, reloaded when it changes
+    pub config_path: Option<String>,
+    // plays every game from this seed instead of a new one each time
+    pub seed: Option<u64>,
+    // plays this recording instead of reading the keyboard
+    pub replay: Option<Replay>,
+    // saves the input of every game to this file
+    pub record: Option<String>,
+    // no window, the game starts right away and steps as fast as it goes
+    pub headless: bool,
+    pub debug: bool,
+}
+
+/**
+ * The whole game. The app needs DefaultPlugins and ShapePlugin when it isn't headless,
+ * MinimalPlugins and AssetPlugin when it is.
+ */
+#[derive(Default)]
+pub struct AsteroidsGamePlugin {
+    pub settings: GameSettings,
+}
+
+impl Plugin for AsteroidsGamePlugin {
+    fn build(&self, app: &mut App) {
+        let settings = &self.settings;
+        // a replay plays out the game it was recorded from
+        let seed = match &settings.replay {
+            Some(replay) => Some(replay.seed),
+            None => settings.seed,
+        };
+        // one thread keeps the order of systems touching the same bodies the same on every run
+        let mut timestep = FixedTimestepStage::new(TIME_STEP, SystemStage::single_threaded());
+        if settings.headless {
+            timestep = timestep.lockstep();
+        }
+
+        app.insert_resource(Debug(settings.debug))
+            .init_resource::<Lives>()
+            .init_resource::<Score>()
+            .init_resource::<Combo>()
+            .init_resource::<ControlInput>()
+            .init_resource::<SimulationClock>()
+            .add_event::<ScoreEvent>()
+            .add_plugin(RandomPlugin { seed })
+            .add_plugin(ConfigPlugin {
+                config: settings.config.clone(),
+                path: settings.config_path.clone(),
+            })
+            .add_stage_before(CoreStage::Update, FixedUpdate, timestep)
+            .add_state(match settings.headless {
+                true => AppState::Playing,
+                false => AppState::MainMenu,
+            })
+            .add_system_set(
+                SystemSet::on_enter(AppState::MainMenu).with_system(main_menu_setup_system),
+            )
+            .add_system_set(SystemSet::on_update(AppState::MainMenu).with_system(main_menu_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::MainMenu).with_system(despawn_system::<OverlayText>),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::Playing)
+                    .with_system(setup_ship)
+                    .with_system(new_game_seed_system.before(setup_stars))
+                    .with_system(setup_stars)
+                    .with_system(start_recording_system.after(new_game_seed_system))
+                    .with_system(rewind_replay_system)
+                    .with_system(reset_lives_system)
+                    .with_system(reset_score_system),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing)
+                    .with_system(save_recording_system)
+                    .with_system(despawn_system::<Ship>)
+                    .with_system(despawn_system::<Star>),
+            )
+            .add_system_set(SystemSet::on_enter(AppState::Paused).with_system(paused_setup_system))
+            .add_system_set(SystemSet::on_update(AppState::Paused).with_system(resume_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::Paused).with_system(despawn_system::<OverlayText>),
+            )
+            .add_system_set(
+                SystemSet::on_enter(AppState::GameOver).with_system(game_over_setup_system),
+            )
+            .add_system_set(SystemSet::on_update(AppState::GameOver).with_system(game_over_system))
+            .add_system_set(
+                SystemSet::on_exit(AppState::GameOver).with_system(despawn_system::<OverlayText>),
+            )
+            .add_simulation_system(playback_input_system.before(GameSystem::Input))
+            .add_simulation_system(
+                record_input_system
+                    .after(playback_input_system)
+                    .before(GameSystem::Input),
+            )
+            .add_simulation_system_set(
+                SystemSet::new()
+                    // events are read in the step that sends them, a frame may not run another
+                    .with_system(player_state_system.after(GameSystem::Collision))
+                    .with_system(shield_regen_system)
+                    .with_system(score_system.after(GameSystem::Collision))
+                    .with_system(extra_life_system.after(score_system)),
+            )
+            .add_plugin(MovementPlugin)
+            .add_plugin(CollisionPlugin)
+            .add_plugin(AsteroidPlugin)
+            .add_plugin(ParticlesPlugin)
+            .add_plugin(WeaponsPlugin);
+
+        if let Some(replay) = &settings.replay {
+            app.insert_resource(ReplayPlayer {
+                replay: replay.clone(),
+                step: 0,
+            });
+        }
+        if let Some(path) = &settings.record {
+            app.insert_resource(ReplayRecorder {
+                path: path.clone(),
+                replay: Replay::default(),
+            });
+        }
+
+        if settings.headless {
+            // nothing to draw with, the overlays still need a font
+            app.insert_resource(FontAtlas(Handle::default()))
+                .init_resource::<Input<KeyCode>>()
+                .init_resource::<Deaths>()
+                .add_system_set(
+                    SystemSet::on_enter(AppState::Playing).with_system(reset_deaths_system),
+                )
+                .add_system(death_count_system);
+            return;
+        }
+
+        app.add_plugin(HudPlugin).add_system_set(
+            SystemSet::on_update(AppState::Playing)
+                .with_system(pause_system)
+                .with_system(flick_system),
+        );
+        if settings.replay.is_none() {
+            app.add_system_to_stage(
+                CoreStage::PreUpdate,
+                control_input_system.after(InputSystem),
+            );
+        }
+    }
+}
+
+pub fn player_state_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<GameConfig>,
+    mut query: Query<(
+        Entity,
+        &mut Ship,
+        &mut Transform,
+        &mut Visibility,
+        Option<&mut Interpolated>,
+    )>,
+    mut ev_death: EventReader<PlayerDeathEvent>,
+    mut lives: ResMut<Lives>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let delta = time.delta();
+    for (entity, mut ship, mut transform, mut visibility, interpolated) in query.iter_mut() {
+        match ship.state {
+            ShipState::Spawning => {
+                ship.timer.tick(delta);
+                if ship.timer.just_finished() {
+                    commands
+                        .entity(entity)
+                        .remove::<Flick>()
+                        .insert(Bounding::from(PLAYER_SIZE / 2.0))
+                        .insert(Mass::disc(PLAYER_SIZE / 2.0, config.ship.density))
+                        .insert(SteeringControl::from(Angle::degrees(config.ship.steering)))
+                        .insert(Stabilizer::from(config.ship.stabilizer.to_radians()))
+                        .insert(Drive::new(
+                            config.ship.propulsion_force,
+                            config.ship.reverse_force,
+                        ))
+                        .insert(SideThrusters::new(config.ship.side_thruster_force))
+                        .insert(Loadout::new(config.weapons.clone()))
+                        .insert(Heat::new(config.ship.cooling))
+                        .insert(Velocity::default())
+                        .insert(AngularVelocity::default())
+                        .insert(Damage(config.ship.damage))
+                        .insert(Hull::new(HULL_MAX))
+                        .insert(Shield::new(SHIELD_MAX));
+                    ship.state = ShipState::Alive;
+                    visibility.is_visible = true;
+                }
+            }
+            ShipState::Dead => {
+                ship.timer.tick(delta);
+                if ship.timer.just_finished() {
+                    if lives.remaining == 0 {
+                        let _ = state.set(AppState::GameOver);
+                        continue;
+                    }
+
+                    transform.rotation = Quat::from_rotation_z(180.0_f32.to_radians());
+                    transform.translation.x = 0.0;
+                    transform.translation.y = 0.0;
+                    // not drawn sliding over from the wreck
+                    if let Some(mut interpolated) = interpolated {
+                        interpolated.snap(*transform);
+                    }
+                    commands.entity(entity).insert(Flick {
+                        duration: Timer::new(Duration::from_secs(2), false),
+                        switch_timer: Timer::new(Duration::from_millis(200), true),
+                    });
+                    *ship = Ship {
+                        state: ShipState::Spawning,
+                        timer: Timer::from_seconds(1.0, false),
+                    };
+                    visibility.is_visible = true;
+                }
+            }
+            ShipState::Alive => {
+                // several hits in the same frame still only cost one life
+                if ev_death.iter().last().is_some() {
+                    commands
+                        .entity(entity)
+                        .remove::<Bounding>()
+                        .remove::<SteeringControl>()
+                        .remove::<Stabilizer>()
+                        .remove::<Drive>()
+                        .remove::<SideThrusters>()
+                        .remove::<Loadout>()
+                        .remove::<Heat>()
+                        .remove::<Velocity>()
+                        .remove::<AngularVelocity>()
+                        .remove::<Damage>();
+                    *ship = Ship {
+                        state: ShipState::Dead,
+                        timer: Timer::from_seconds(2.0, false),
+                    };
+                    visibility.is_visible = false;
+                    lives.remaining = lives.remaining.saturating_sub(1);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct Star;
+
+#[derive(Bundle)]
+pub struct StarBundle {
+    pub star: Star,
+    #[bundle]
+    pub shape: ShapeBundle,
+    // flick: Flick, blink system?
+}
+
+pub fn setup_stars(mut commands: Commands, mut rng: Local<Random<Stars>>) {
+    for _ in 0..150 {
+        let pos = vec2(
+            rng.gen_range(FRAME_START_X..FRAME_END_X),
+            rng.gen_range(FRAME_START_Y..FRAME_END_Y),
+        );
+
+        let shape = shapes::Circle {
+            radius: rng.gen_range(0.01..CANNON_BULLET_RADIUS),
+            ..Default::default()
+        };
+
+        let cor = if rng.gen_ratio(1, 2) { LIGHT } else { ESCURO };
+        let _star = commands.spawn().insert_bundle(StarBundle {
+            star: Star,
+            shape: (GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Outlined {
+                    outline_mode: StrokeMode::new(cor, POLY_LINE_WIDTH),
+                    fill_mode: FillMode::color(cor),
+                },
+                Transform {
+                    translation: vec3(pos.x, pos.y, 0.0),
+                    ..Default::default()
+                },
+            )),
+        });
+    }
+}
+
+pub fn setup_ship(mut commands: Commands, config: Res<GameConfig>) {
+    let shape = shapes::Polygon {
+        points: ship_points(),
+        closed: false,
+    };
+    let _player = commands
+        .spawn()
+        .insert_bundle(
+            (GeometryBuilder::build_as(
+                &shape,
+                DrawMode::Outlined {
+                    outline_mode: StrokeMode::new(LIGHT, POLY_LINE_WIDTH),
+                    fill_mode: FillMode::color(LIGHT),
+                },
+                Transform {
+                    rotation: Quat::from_rotation_z(180.0_f32.to_radians()),
+                    ..Default::default()
+                },
+            )),
+        )
+        .insert(Ship {
+            state: ShipState::Spawning,
+            timer: Timer::new(Duration::from_millis(1), false),
+        })
+        .insert(Flick {
+            duration: Timer::new(Duration::from_secs(2), false),
+            switch_timer: Timer::new(Duration::from_millis(200), true),
+        })
+        .insert(BoundaryWrap)
+        .insert(Velocity::default())
+        .insert(SpeedLimit::from(config.ship.speed_limit))
+        .insert(AngularVelocity::default())
+        .insert(Damping::from(config.ship.damping));
+}
+
+pub fn destruction_system(mut commands: Commands, mut ev_hit: EventReader<DestructionEvent>) {
+    for DestructionEvent { entity } in ev_hit.iter() {
+        commands.entity(*entity).despawn_recursive();
+    }
+}
+
+pub struct DestructionEvent {
+    pub entity: Entity,
+}
+
+pub struct PlayerDeathEvent {}
+
+pub fn polygon(center: Vec2, r: f32, amount: i32) -> Vec<Vec2> {
+    let mut points = Vec::new();
+    let angle_inc = 360.0 / amount as f32;
+
+    for i in 1..=amount {
+        let rot = (angle_inc * i as f32).to_radians();
+        points.push(vec2(center.x + r * rot.sin(), center.y - r * rot.cos()));
+    }
+
+    points
+}
+
+pub fn ship_points() -> Vec<Vec2> {
+    let rot = 0.0_f32.to_radians();
+    let h = PLAYER_SIZE; // ship height
+    let w = PLAYER_SIZE; // ship width
+
+    let v1 = vec2(rot.sin() * h / 2., -rot.cos() * h / 2.);
+    let v2 = vec2(
+        -rot.cos() * w / 2. - rot.sin() * h / 2.,
+        -rot.sin() * w / 2. + rot.cos() * h / 2.,
+    );
+    let v3 = vec2(
+        rot.cos() * w / 2. - rot.sin() * h / 2.,
+        rot.sin() * w / 2. + rot.cos() * h / 2.,
+    );
+    let v4 = vec2(
+        -rot.cos() * w / 1.5 - rot.sin() * h / 1.5,
+        -rot.sin() * w / 1.5 + rot.cos() * h / 1.5,
+    );
+    let v5 = vec2(
+        rot.cos() * w / 1.5 - rot.sin() * h / 1.5,
+        rot.sin() * w / 1.5 + rot.cos() * h / 1.5,
+    );
+
+    vec![v1, v2, v4, v2, v3, v5, v3, v1]
+}
+
+pub fn flick_system(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Visibility, &mut Flick)>,
+    time: Res<Time>,
+) {
+    for (entity, mut visibility, mut flick) in query.iter_mut() {
+        flick.duration.tick(time.delta());
+        flick.switch_timer.tick(time.delta());
+
+        if flick.duration.finished() {
+            visibility.is_visible = true;
+            commands.entity(entity).remove::<Flick>();
+        } else if flick.switch_timer.just_finished() {
+            visibility.is_visible = !visibility.is_visible;
+        }
+    }
+}
+
+#[derive(Debug, Component, Default, From)]
+pub struct Flick {
+    pub switch_timer: Timer,
+    pub duration: Timer,
+}
+
+#[derive(Debug, Component, Default)]
+pub struct Ship {
+    pub state: ShipState,
+    pub timer: Timer,
+}
+
+#[derive(Debug, Component)]
+pub struct TimedRemoval(pub Timer);
+
+#[derive(Debug, Component)]
+pub struct Darken(pub Timer);
+#[derive(Debug, Component)]
+pub struct Shrink(pub Timer);
+
+#[derive(Debug, Component)]
+pub struct DelayedVisibility(pub Timer);
+
+// impl Ship {
+//     fn alive() -> Self {
+//         Ship {
+//             state: ShipState::Alive,
+//         }
+//     }
+
+//     fn dead(duration: Duration) -> Self {
+//         Ship {
+//             state: ShipState::Dead(duration.),
+//         }
+//     }
+
+//     fn spawn(duration: Duration) -> Self {
+//         Ship {
+//             state: ShipState::Spawning(duration),
+//         }
+//     }
+// }
+
+#[derive(Debug, Clone)]
+pub enum ShipState {
+    Alive,
+    Dead,
+    Spawning,
+}
+
+impl Default for ShipState {
+    fn default() -> Self {
+        ShipState::Alive
+    }
+}
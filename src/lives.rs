@@ -0,0 +1,37 @@
+use bevy::prelude::*;
+
+use crate::score::Score;
+
+pub const STARTING_LIVES: u32 = 3;
+// the HUD only has room for a single digit
+pub const MAX_LIVES: u32 = 9;
+pub const EXTRA_LIFE_SCORE: u32 = 10_000;
+
+#[derive(Debug)]
+pub struct Lives {
+    pub remaining: u32,
+    pub next_extra_life: u32,
+}
+
+impl Default for Lives {
+    fn default() -> Self {
+        Lives {
+            remaining: STARTING_LIVES,
+            next_extra_life: EXTRA_LIFE_SCORE,
+        }
+    }
+}
+
+pub fn reset_lives_system(mut lives: ResMut<Lives>) {
+    *lives = Lives::default();
+}
+
+/**
+ * Awards an extra life every time the score passes a multiple of EXTRA_LIFE_SCORE
+ */
+pub fn extra_life_system(score: Res<Score>, mut lives: ResMut<Lives>) {
+    while score.0 >= lives.next_extra_life {
+        lives.remaining = (lives.remaining + 1).min(MAX_LIVES);
+        lives.next_extra_life += EXTRA_LIFE_SCORE;
+    }
+}
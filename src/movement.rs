@@ -1,3 +1,11 @@
+use std::f32::consts::PI;
+
+use crate::boundary::{boundary_removal_system, boundary_wrapping_system, ghost_system, Ghost};
+use crate::controls::ControlInput;
+use crate::geometry::{polygon_area, polygon_inertia};
+use crate::state::{despawn_system, AppState};
+use crate::timestep::SimulationApp;
+use crate::{GameSystem, Ship};
 use bevy::ecs::component::Component;
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::tess::math::Angle;
@@ -9,9 +17,83 @@ pub struct Velocity(pub Vec2);
 #[derive(Debug, Component, Default, Deref, DerefMut, From)]
 pub struct AngularVelocity(pub f32);
 
+/**
+ * Steering, engines and integrating the motion of everything that moves, wrapping around the
+ * frame edges
+ */
+pub struct MovementPlugin;
+
+impl Plugin for MovementPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_simulation_system_set(
+            SystemSet::new()
+                .label(GameSystem::Input)
+                .with_system(steering_control_system)
+                .with_system(drive_control_system)
+                .with_system(side_thruster_control_system),
+        )
+        .add_simulation_system_set(
+            SystemSet::new()
+                .label(GameSystem::Movement)
+                .with_system(movement_system)
+                .with_system(drive_system)
+                .with_system(side_thruster_system)
+                .with_system(damping_system)
+                // caps what the engines add before the step is taken
+                .with_system(
+                    speed_limit_system
+                        .after(drive_system)
+                        .after(side_thruster_system)
+                        .before(movement_system),
+                )
+                .after(GameSystem::Input),
+        )
+        .add_simulation_system_set(
+            SystemSet::new()
+                .label(GameSystem::Boundary)
+                .with_system(boundary_removal_system)
+                .after(GameSystem::Movement),
+        )
+        .add_simulation_system(boundary_wrapping_system.after(GameSystem::Movement))
+        // ghosts follow the interpolated transforms, so they are drawn every frame
+        .add_system_set(SystemSet::on_update(AppState::Playing).with_system(ghost_system))
+        .add_system_set(SystemSet::on_exit(AppState::Playing).with_system(despawn_system::<Ghost>));
+    }
+}
+
+// damping and forces are tuned per step of a game running at this rate
+pub const REFERENCE_RATE: f32 = 60.0;
+
 #[derive(Debug, Component, Default, Deref, DerefMut, From)]
 pub struct Damping(pub f32);
 
+/**
+ * How hard a body is to push around, and to spin
+ */
+#[derive(Debug, Component, Clone, Copy)]
+pub struct Mass {
+    pub mass: f32,
+    // moment of inertia around the body's origin
+    pub inertia: f32,
+}
+
+impl Mass {
+    pub fn disc(radius: f32, density: f32) -> Self {
+        let mass = PI * radius.powi(2) * density;
+        Mass {
+            mass,
+            inertia: mass * radius.powi(2) / 2.0,
+        }
+    }
+
+    pub fn polygon(points: &[Vec2], density: f32) -> Self {
+        Mass {
+            mass: polygon_area(points) * density,
+            inertia: polygon_inertia(points) * density,
+        }
+    }
+}
+
 #[derive(Debug, Component, Default, Deref, DerefMut, From)]
 pub struct SpeedLimit(pub f32);
 
@@ -62,6 +144,13 @@ impl SideThrusters {
 #[derive(Debug, Component, Default, Deref, DerefMut, From)]
 pub struct SteeringControl(Angle);
 
+/**
+ * How fast the steering can change the ship's spin, in radians per second, per second.
+ * Spin picked up from collisions fades out at the same rate.
+ */
+#[derive(Debug, Component, Default, Deref, DerefMut, From)]
+pub struct Stabilizer(pub f32);
+
 pub fn movement_system(
     time: Res<Time>,
     mut query: Query<(&mut Transform, Option<&AngularVelocity>, Option<&Velocity>)>,
@@ -77,17 +166,25 @@ pub fn movement_system(
     }
 }
 
-pub fn damping_system(mut query: Query<(&mut Velocity, &Damping)>) {
+pub fn damping_system(time: Res<Time>, mut query: Query<(&mut Velocity, &Damping)>) {
+    // the damping is the fraction kept per reference step, scaled to the actual step
+    let steps = time.delta_seconds() * REFERENCE_RATE;
     for (mut velocity, damping) in query.iter_mut() {
-        velocity.0 *= damping.0;
+        velocity.0 *= damping.0.powf(steps);
+    }
+}
+
+pub fn speed_limit_system(mut query: Query<(&mut Velocity, &SpeedLimit)>) {
+    for (mut velocity, limit) in query.iter_mut() {
+        velocity.0 = velocity.0.clamp_length_max(limit.0);
     }
 }
 
-pub fn drive_control_system(mut query: Query<&mut Drive>, keyboard: Res<Input<KeyCode>>) {
+pub fn drive_control_system(mut query: Query<&mut Drive, With<Ship>>, input: Res<ControlInput>) {
     for mut drive in query.iter_mut() {
-        drive.mode = if keyboard.any_pressed([KeyCode::Up, KeyCode::W]) {
+        drive.mode = if input.propulsion {
             DriveMode::Propulsion
-        } else if keyboard.any_pressed([KeyCode::Down, KeyCode::S]) {
+        } else if input.reverse {
             DriveMode::Reverse
         } else {
             DriveMode::Off
@@ -97,12 +194,12 @@ pub fn drive_control_system(mut query: Query<&mut Drive>, keyboard: Res<Input<Ke
 
 pub fn side_thruster_control_system(
     mut query: Query<&mut SideThrusters>,
-    keyboard: Res<Input<KeyCode>>,
+    input: Res<ControlInput>,
 ) {
     for mut thrusters in query.iter_mut() {
-        thrusters.mode = if keyboard.pressed(KeyCode::Q) {
+        thrusters.mode = if input.thrust_left {
             ThrustersMode::Left
-        } else if keyboard.pressed(KeyCode::E) {
+        } else if input.thrust_right {
             ThrustersMode::Right
         } else {
             ThrustersMode::Off
@@ -110,27 +207,32 @@ pub fn side_thruster_control_system(
     }
 }
 
-pub fn drive_system(mut query: Query<(&mut Velocity, &Transform, &Drive)>) {
+pub fn drive_system(time: Res<Time>, mut query: Query<(&mut Velocity, &Transform, &Drive)>) {
+    let steps = time.delta_seconds() * REFERENCE_RATE;
     for (mut velocity, transform, drive) in query.iter_mut() {
         match drive.mode {
-            DriveMode::Off => return,
+            DriveMode::Off => {}
             DriveMode::Propulsion => {
                 // what the fuck is this quat shit
                 // changed from Vec3::X to -Vec::Y and now this shit works wtf?
                 let direction = transform.rotation * -Vec3::Y;
-                velocity.x += direction.x * drive.propulsion_force;
-                velocity.y += direction.y * drive.propulsion_force;
+                velocity.x += direction.x * drive.propulsion_force * steps;
+                velocity.y += direction.y * drive.propulsion_force * steps;
             }
             DriveMode::Reverse => {
                 let direction = transform.rotation * -Vec3::Y;
-                velocity.x += -(direction.x * drive.reverse_force);
-                velocity.y += -(direction.y * drive.reverse_force);
+                velocity.x += -(direction.x * drive.reverse_force * steps);
+                velocity.y += -(direction.y * drive.reverse_force * steps);
             }
         }
     }
 }
 
-pub fn side_thruster_system(mut query: Query<(&mut Velocity, &Transform, &SideThrusters)>) {
+pub fn side_thruster_system(
+    time: Res<Time>,
+    mut query: Query<(&mut Velocity, &Transform, &SideThrusters)>,
+) {
+    let steps = time.delta_seconds() * REFERENCE_RATE;
     for (mut velocity, transform, thruster) in query.iter_mut() {
         match thruster.mode {
             ThrustersMode::Off => {}
@@ -138,31 +240,40 @@ pub fn side_thruster_system(mut query: Query<(&mut Velocity, &Transform, &SideTh
                 let angle = 90.0_f32.to_radians();
                 let rot = transform.rotation;
                 let direction = rot.mul_quat(Quat::from_rotation_z(angle)) * -Vec3::Y;
-                velocity.x += direction.x * thruster.force;
-                velocity.y += direction.y * thruster.force;
+                velocity.x += direction.x * thruster.force * steps;
+                velocity.y += direction.y * thruster.force * steps;
             }
             ThrustersMode::Right => {
                 let angle = 90.0_f32.to_radians();
                 let rot = transform.rotation;
                 let direction = rot.mul_quat(Quat::from_rotation_z(angle)) * -Vec3::Y;
-                velocity.x += -(direction.x * thruster.force);
-                velocity.y += -(direction.y * thruster.force);
+                velocity.x += -(direction.x * thruster.force * steps);
+                velocity.y += -(direction.y * thruster.force * steps);
             }
         }
     }
 }
 
 pub fn steering_control_system(
-    mut query: Query<(&mut AngularVelocity, &SteeringControl)>,
-    keyboard: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut query: Query<(&mut AngularVelocity, &SteeringControl, Option<&Stabilizer>)>,
+    input: Res<ControlInput>,
 ) {
-    for (mut angular_velocity, steering_control) in query.iter_mut() {
-        if keyboard.any_pressed([KeyCode::Left, KeyCode::A]) {
-            *angular_velocity = AngularVelocity::from(steering_control.0.get());
-        } else if keyboard.any_pressed([KeyCode::Right, KeyCode::D]) {
-            *angular_velocity = AngularVelocity::from(-steering_control.0.get());
+    for (mut angular_velocity, steering_control, stabilizer) in query.iter_mut() {
+        let target = if input.steer_left {
+            steering_control.0.get()
+        } else if input.steer_right {
+            -steering_control.0.get()
         } else {
-            *angular_velocity = AngularVelocity::from(0.0);
-        }
+            0.0
+        };
+
+        angular_velocity.0 = match stabilizer {
+            Some(stabilizer) => {
+                let max_change = stabilizer.0 * time.delta_seconds();
+                angular_velocity.0 + (target - angular_velocity.0).clamp(-max_change, max_change)
+            }
+            None => target,
+        };
     }
 }
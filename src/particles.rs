@@ -1,16 +1,23 @@
-use crate::movement::ThrustersMode;
+use crate::{
+    config::GameConfig,
+    movement::ThrustersMode,
+    state::{despawn_system, AppState},
+    timestep::SimulationApp,
+    GameSystem,
+};
 
 use super::{
     movement::{Drive, DriveMode, SideThrusters},
-    random::Random,
-    Color, Damping, Darken, DelayedVisibility, Shrink, TimedRemoval, Velocity, DARK, LIGHT,
-    PLAYER_DAMPING, PLAYER_SIZE, POLY_LINE_WIDTH,
+    random::{BallParticles, GasExhaust, GrainParticles, PropulsionExhaust, Random},
+    Bounding, Color, Damping, Darken, DelayedVisibility, Shrink, TimedRemoval, Velocity, DARK,
+    LIGHT, PLAYER_SIZE, POLY_LINE_WIDTH,
 };
 use bevy::{
     math::{vec2, vec3},
     prelude::{
-        BuildChildren, Commands, Component, Entity, EventReader, Local, Query, Res, Time,
-        Transform, Vec2, Vec3, Visibility, Without,
+        App, BuildChildren, Commands, Component, Entity, EventReader, Local,
+        ParallelSystemDescriptorCoercion, Plugin, Query, Res, SystemSet, Time, Transform, Vec2,
+        Vec3, Visibility, Without,
     },
     time::Timer,
 };
@@ -21,21 +28,21 @@ use bevy_prototype_lyon::{
 use rand::Rng;
 use std::{ops::Range, time::Duration};
 
-const GRAIN_RADIUS: f32 = 0.3;
-const PARTICLE_DAMPING: f32 = 0.992;
+pub const GRAIN_RADIUS: f32 = 0.3;
+pub const PARTICLE_DAMPING: f32 = 0.992;
 
 const EXHAUST_SIZE: f32 = 10.0;
 const GAS_EXHAUST_SIZE: f32 = 15.0;
 const GAS_LINE_WIDTH: f32 = 0.1;
 
 // in seconds
-const GAS_EXHAUST_TIMEOUT: f32 = 0.15;
-const GAS_EXHAUST_LIVE_TIME: f32 = 0.2;
+pub const GAS_EXHAUST_TIMEOUT: f32 = 0.15;
+pub const GAS_EXHAUST_LIVE_TIME: f32 = 0.2;
 const GAS_EXHAUST_SIZE_RANGE: Range<f32> = (GAS_EXHAUST_SIZE / 1.5)..(GAS_EXHAUST_SIZE * 1.25);
 
-const EXHAUST_TIMEOUT: f32 = 0.15;
+pub const EXHAUST_TIMEOUT: f32 = 0.15;
 const EXHAUST_SHRINK_TIMEOUT: f32 = 0.05;
-const EXHAUST_LIVE_TIME: f32 = 2.0;
+pub const EXHAUST_LIVE_TIME: f32 = 2.0;
 
 const EXHAUST_VEL_MUT: f32 = -0.3;
 const EXHAUST_POS_X_RANGE: Range<f32> = -(PLAYER_SIZE / 2.0)..(PLAYER_SIZE / 2.0);
@@ -58,6 +65,42 @@ pub struct BallParticleSpawnEvent {
     pub dir_vel: Vec2,
 }
 
+/**
+ * Particle effects and their timing
+ */
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<GrainParticleSpawnEvent>()
+            .add_event::<BallParticleSpawnEvent>()
+            .add_simulation_system_set(
+                SystemSet::new()
+                    .label(GameSystem::Particles)
+                    .with_system(grain_spawn_system)
+                    .with_system(ball_spawn_system)
+                    .after(GameSystem::Collision),
+            )
+            .add_simulation_system_set(
+                SystemSet::new()
+                    .label(GameSystem::Despawning)
+                    .with_system(timed_removal_system)
+                    .after(GameSystem::Movement),
+            )
+            .add_simulation_system_set(
+                SystemSet::new()
+                    .with_system(darken_system.before(GameSystem::Despawning))
+                    .with_system(shrink_system.before(GameSystem::Despawning))
+                    .with_system(delayed_spawn_system.before(GameSystem::Despawning))
+                    .with_system(propulsion_exhaust_system.after(GameSystem::Movement))
+                    .with_system(gas_exhaust_system.after(GameSystem::Movement)),
+            )
+            .add_system_set(
+                SystemSet::on_exit(AppState::Playing).with_system(despawn_system::<TimedRemoval>),
+            );
+    }
+}
+
 pub fn darken_system(
     mut query: Query<(&mut DrawMode, &mut Darken, Without<DelayedVisibility>)>,
     time: Res<Time>,
@@ -80,6 +123,35 @@ pub fn darken_system(
     }
 }
 
+pub fn timed_removal_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut TimedRemoval, Without<DelayedVisibility>)>,
+) {
+    for (entity, mut removal, _) in query.iter_mut() {
+        removal.0.tick(time.delta());
+
+        if removal.0.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+pub fn delayed_spawn_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut DelayedVisibility, &mut Visibility)>,
+) {
+    for (entity, mut delay, mut visibility) in query.iter_mut() {
+        delay.0.tick(time.delta());
+
+        if delay.0.finished() {
+            commands.entity(entity).remove::<DelayedVisibility>();
+            visibility.is_visible = true;
+        }
+    }
+}
+
 /**
  * Shrink the component by subtracting the scale vector each time the timer finishes
  */
@@ -99,7 +171,7 @@ pub fn shrink_system(mut shrinking: Query<(&mut Transform, &mut Shrink)>, time:
  */
 pub fn ball_spawn_system(
     mut commands: Commands,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<BallParticles>>,
     mut ev_ball: EventReader<BallParticleSpawnEvent>,
 ) {
     for BallParticleSpawnEvent {
@@ -169,8 +241,9 @@ pub fn ball_spawn_system(
  */
 pub fn grain_spawn_system(
     mut commands: Commands,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<GrainParticles>>,
     mut ev_grain: EventReader<GrainParticleSpawnEvent>,
+    config: Res<GameConfig>,
 ) {
     for GrainParticleSpawnEvent {
         pos,
@@ -180,7 +253,7 @@ pub fn grain_spawn_system(
     } in ev_grain.iter()
     {
         let shape = shapes::Circle {
-            radius: GRAIN_RADIUS,
+            radius: config.particles.grain_radius,
             ..Default::default()
         };
 
@@ -216,7 +289,7 @@ pub fn grain_spawn_system(
                 )))
                 .insert(Darken(Timer::new(Duration::from_millis(100), false)))
                 .insert(Velocity::from(vel))
-                .insert(Damping::from(PARTICLE_DAMPING));
+                .insert(Damping::from(config.particles.damping));
         }
     }
 }
@@ -230,8 +303,9 @@ pub struct GasExhaustTimer(pub Timer);
 pub fn gas_exhaust_system(
     mut gas_valves: Query<(Entity, &SideThrusters, Option<&mut GasExhaustTimer>)>,
     mut commands: Commands,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<GasExhaust>>,
     time: Res<Time>,
+    config: Res<GameConfig>,
 ) {
     for (entity, side_thrusters, timer) in gas_valves.iter_mut() {
         match side_thrusters.mode {
@@ -274,7 +348,7 @@ pub fn gas_exhaust_system(
                             .spawn()
                             .insert_bundle(geo)
                             .insert(TimedRemoval(Timer::from_seconds(
-                                GAS_EXHAUST_LIVE_TIME,
+                                config.particles.gas_exhaust_lifetime,
                                 false,
                             )))
                             .id();
@@ -284,7 +358,7 @@ pub fn gas_exhaust_system(
                     commands
                         .entity(entity)
                         .insert(GasExhaustTimer(Timer::from_seconds(
-                            GAS_EXHAUST_TIMEOUT,
+                            config.particles.gas_exhaust_interval,
                             true,
                         )));
                 }
@@ -296,38 +370,43 @@ pub fn gas_exhaust_system(
 /**
  * Particle effect that creates a cross particle that shrinks and fades
  */
+#[allow(clippy::type_complexity)]
 pub fn propulsion_exhaust_system(
     mut drive_engines: Query<(
         Entity,
         &Velocity,
         &Transform,
         &Drive,
+        Option<&Bounding>,
         Option<&mut ExhaustTimer>,
     )>,
     mut commands: Commands,
-    mut rng: Local<Random>,
+    mut rng: Local<Random<PropulsionExhaust>>,
     time: Res<Time>,
+    config: Res<GameConfig>,
 ) {
-    for (entity, velocity, transform, drive, timer) in drive_engines.iter_mut() {
+    for (entity, velocity, transform, drive, bounding, timer) in drive_engines.iter_mut() {
         if drive.mode == DriveMode::Off {
             if let Some(_) = timer {
                 commands.entity(entity).remove::<ExhaustTimer>();
             }
 
-            return;
+            continue;
         }
 
         if let Some(mut timer) = timer {
             timer.0.tick(time.delta());
             if (timer.0.just_finished()) {
+                // the exhaust is laid out for the ship, smaller engines leave a smaller trail
+                let scale = bounding.map_or(1.0, |bounding| bounding.0 / (PLAYER_SIZE / 2.0));
                 let rot = transform.rotation;
                 let pos = vec3(
-                    rng.gen_range(EXHAUST_POS_X_RANGE),
-                    rng.gen_range(EXHAUST_POS_Y_RANGE),
+                    rng.gen_range(EXHAUST_POS_X_RANGE) * scale,
+                    rng.gen_range(EXHAUST_POS_Y_RANGE) * scale,
                     1.0,
                 );
 
-                let size = rng.gen_range(EXHAUST_SIZE_RANGE);
+                let size = rng.gen_range(EXHAUST_SIZE_RANGE) * scale;
                 let horisontal = shapes::Line(vec2(-size / 2.0, 0.0), vec2(size / 2.0, 0.0));
                 let vertical = shapes::Line(vec2(0.0, -size / 2.0), vec2(0.0, size / 2.0));
 
@@ -349,14 +428,20 @@ pub fn propulsion_exhaust_system(
                     .spawn()
                     .insert_bundle(exhaust)
                     .insert(Velocity(velocity.0 * EXHAUST_VEL_MUT))
-                    .insert(TimedRemoval(Timer::from_seconds(EXHAUST_LIVE_TIME, false)))
-                    .insert(Damping::from(PLAYER_DAMPING))
+                    .insert(TimedRemoval(Timer::from_seconds(
+                        config.particles.exhaust_lifetime,
+                        false,
+                    )))
+                    .insert(Damping::from(config.ship.damping))
                     .insert(Shrink(Timer::from_seconds(EXHAUST_SHRINK_TIMEOUT, true)));
             }
         } else {
             commands
                 .entity(entity)
-                .insert(ExhaustTimer(Timer::from_seconds(EXHAUST_TIMEOUT, true)));
+                .insert(ExhaustTimer(Timer::from_seconds(
+                    config.particles.exhaust_interval,
+                    true,
+                )));
         }
     }
 }
@@ -1,22 +1,166 @@
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
 use bevy::prelude::*;
 use rand::{prelude::SmallRng, SeedableRng};
 
-pub struct RandomPlugin;
+pub struct RandomPlugin {
+    // fixed seed for every game, a new one is drawn for each game otherwise
+    pub seed: Option<u64>,
+}
 
 impl Plugin for RandomPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Random(SmallRng::from_entropy()));
+        app.insert_resource(Seed::new(self.seed));
+    }
+}
+
+/**
+ * Names a random stream. Every system draws from its own stream, derived from the seed of the
+ * game and this name, so adding or reordering systems doesn't change what the others draw.
+ */
+pub trait Stream: Send + Sync + 'static {
+    const NAME: &'static str;
+}
+
+macro_rules! streams {
+    ($($stream:ident => $name:literal),* $(,)?) => {
+        $(
+            pub struct $stream;
+
+            impl Stream for $stream {
+                const NAME: &'static str = $name;
+            }
+        )*
+    };
+}
+
+// renaming a stream changes the games every recorded seed plays out
+streams! {
+    GameUi => "game_ui",
+    Stars => "stars",
+    AsteroidSpawning => "asteroid_spawning",
+    AsteroidSplitting => "asteroid_splitting",
+    AsteroidGeneration => "asteroid_generation",
+    BallParticles => "ball_particles",
+    GrainParticles => "grain_particles",
+    GasExhaust => "gas_exhaust",
+    PropulsionExhaust => "propulsion_exhaust",
+    WeaponSpread => "weapon_spread",
+}
+
+#[derive(Debug)]
+struct SharedSeed {
+    seed: AtomicU64,
+    // bumped for every new game, streams reseed when they see it change
+    game: AtomicU64,
+}
+
+/**
+ * The seed of the current game
+ */
+#[derive(Debug, Clone)]
+pub struct Seed {
+    fixed: Option<u64>,
+    shared: Arc<SharedSeed>,
+}
+
+impl Seed {
+    pub fn new(fixed: Option<u64>) -> Self {
+        Seed {
+            fixed,
+            shared: Arc::new(SharedSeed {
+                seed: AtomicU64::new(fixed.unwrap_or_else(rand::random)),
+                game: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.shared.seed.load(Ordering::SeqCst)
+    }
+
+    fn game(&self) -> u64 {
+        self.shared.game.load(Ordering::SeqCst)
     }
+
+    /**
+     * Starts every stream over, from the fixed seed or a new one
+     */
+    pub fn new_game(&self) {
+        let seed = self.fixed.unwrap_or_else(rand::random);
+        self.shared.seed.store(seed, Ordering::SeqCst);
+        self.shared.game.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+pub fn new_game_seed_system(seed: Res<Seed>) {
+    seed.new_game();
+}
+
+/**
+ * FNV-1a, picked because it gives the same hash on every platform and Rust version
+ */
+fn stream_seed(seed: u64, name: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in name.bytes().chain(seed.to_le_bytes()) {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+
+    hash
+}
+
+/**
+ * A random stream, meant to be used as a system Local
+ */
+pub struct Random<S: Stream> {
+    rng: SmallRng,
+    seed: Seed,
+    game: u64,
+    stream: PhantomData<S>,
 }
 
-#[derive(Debug, Deref, DerefMut)]
-pub struct Random(SmallRng);
+impl<S: Stream> Random<S> {
+    pub fn new(seed: Seed) -> Self {
+        Random {
+            rng: SmallRng::seed_from_u64(stream_seed(seed.value(), S::NAME)),
+            game: seed.game(),
+            seed,
+            stream: PhantomData,
+        }
+    }
+}
 
-impl FromWorld for Random {
+impl<S: Stream> FromWorld for Random<S> {
     fn from_world(world: &mut World) -> Self {
-        let rng = world
-            .get_resource_mut::<Random>()
-            .expect("Random resource not found");
-        Random(SmallRng::from_rng(rng.clone()).unwrap())
+        let seed = world
+            .get_resource::<Seed>()
+            .expect("Seed resource not found");
+        Random::new(seed.clone())
+    }
+}
+
+impl<S: Stream> Deref for Random<S> {
+    type Target = SmallRng;
+
+    fn deref(&self) -> &Self::Target {
+        &self.rng
+    }
+}
+
+impl<S: Stream> DerefMut for Random<S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if self.game != self.seed.game() {
+            *self = Random::new(self.seed.clone());
+        }
+
+        &mut self.rng
     }
 }
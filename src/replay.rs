@@ -0,0 +1,180 @@
+use std::{fmt, fs, io};
+
+use bevy::prelude::*;
+
+use crate::{controls::ControlInput, random::Seed};
+
+const MAGIC: &[u8; 4] = b"ASTR";
+// 2 widened the input bits to u16
+const VERSION: u8 = 2;
+
+/**
+ * The player input of a single game, one entry per simulation step. Together with the seed it
+ * plays out the same game again.
+ *
+ * On disk: the magic, a version byte, the seed and the step count as little endian u64 and u32,
+ * then runs of identical input as little endian u16 bits followed by a little endian u16 length.
+ */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub seed: u64,
+    pub steps: Vec<ControlInput>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Read(String, io::Error),
+    Write(String, io::Error),
+    Invalid(String, &'static str),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Read(path, err) => write!(f, "could not read {path}: {err}"),
+            ReplayError::Write(path, err) => write!(f, "could not write {path}: {err}"),
+            ReplayError::Invalid(path, reason) => write!(f, "{path}: {reason}"),
+        }
+    }
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Replay {
+            seed,
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.steps.len() as u32).to_le_bytes());
+
+        let mut steps = self.steps.iter().map(ControlInput::bits).peekable();
+        while let Some(bits) = steps.next() {
+            let mut run: u16 = 1;
+            while run < u16::MAX && steps.peek() == Some(&bits) {
+                steps.next();
+                run += 1;
+            }
+            bytes.extend_from_slice(&bits.to_le_bytes());
+            bytes.extend_from_slice(&run.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Replay, &'static str> {
+        let header = MAGIC.len() + 1 + 8 + 4;
+        if bytes.len() < header || &bytes[..MAGIC.len()] != MAGIC {
+            return Err("not a replay");
+        }
+        if bytes[MAGIC.len()] != VERSION {
+            return Err("unsupported replay version");
+        }
+
+        let seed = u64::from_le_bytes(bytes[5..13].try_into().unwrap());
+        let len = u32::from_le_bytes(bytes[13..17].try_into().unwrap()) as usize;
+        let mut replay = Replay::new(seed);
+        for run in bytes[header..].chunks(4) {
+            if run.len() != 4 {
+                return Err("truncated replay");
+            }
+            let input = ControlInput::from_bits(u16::from_le_bytes([run[0], run[1]]));
+            let count = u16::from_le_bytes([run[2], run[3]]) as usize;
+            // checked before expanding, so a broken file can't make it allocate more
+            if replay.steps.len() + count > len {
+                return Err("replay runs longer than its step count");
+            }
+            replay.steps.extend(std::iter::repeat_n(input, count));
+        }
+
+        if replay.steps.len() != len {
+            return Err("truncated replay");
+        }
+
+        Ok(replay)
+    }
+
+    pub fn load(path: &str) -> Result<Replay, ReplayError> {
+        let bytes = fs::read(path).map_err(|err| ReplayError::Read(path.to_string(), err))?;
+        Replay::decode(&bytes).map_err(|reason| ReplayError::Invalid(path.to_string(), reason))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ReplayError> {
+        fs::write(path, self.encode()).map_err(|err| ReplayError::Write(path.to_string(), err))
+    }
+}
+
+/**
+ * Records the input of the current game, written to path when the game ends
+ */
+#[derive(Debug)]
+pub struct ReplayRecorder {
+    pub path: String,
+    pub replay: Replay,
+}
+
+impl ReplayRecorder {
+    pub fn save(&self) {
+        match self.replay.save(&self.path) {
+            Ok(()) => println!("Replay saved to {}", self.path),
+            Err(err) => println!("A problem occured when saving the replay: {err}"),
+        }
+    }
+}
+
+/**
+ * Feeds a recorded game into the controls instead of the keyboard
+ */
+#[derive(Debug)]
+pub struct ReplayPlayer {
+    pub replay: Replay,
+    pub step: usize,
+}
+
+pub fn start_recording_system(seed: Res<Seed>, recorder: Option<ResMut<ReplayRecorder>>) {
+    if let Some(mut recorder) = recorder {
+        recorder.replay = Replay::new(seed.value());
+    }
+}
+
+pub fn record_input_system(input: Res<ControlInput>, recorder: Option<ResMut<ReplayRecorder>>) {
+    if let Some(mut recorder) = recorder {
+        recorder.replay.steps.push(*input);
+    }
+}
+
+pub fn save_recording_system(recorder: Option<Res<ReplayRecorder>>) {
+    if let Some(recorder) = recorder {
+        recorder.save();
+    }
+}
+
+pub fn rewind_replay_system(player: Option<ResMut<ReplayPlayer>>) {
+    if let Some(mut player) = player {
+        player.step = 0;
+    }
+}
+
+pub fn playback_input_system(
+    player: Option<ResMut<ReplayPlayer>>,
+    mut input: ResMut<ControlInput>,
+) {
+    if let Some(mut player) = player {
+        // past the end of the recording the ship is left alone
+        *input = player
+            .replay
+            .steps
+            .get(player.step)
+            .copied()
+            .unwrap_or_default();
+        if player.step == player.replay.steps.len() {
+            println!("Replay finished");
+        }
+        player.step += 1;
+    }
+}
@@ -0,0 +1,59 @@
+use bevy::prelude::*;
+
+// the HUD has room for six digits
+pub const MAX_SCORE: u32 = 999_999;
+pub const MAX_COMBO: u32 = 4;
+// in seconds
+pub const COMBO_WINDOW: f32 = 1.5;
+
+#[derive(Debug, Default)]
+pub struct Score(pub u32);
+
+/**
+ * Kills landed within COMBO_WINDOW of each other multiply their points
+ */
+#[derive(Debug)]
+pub struct Combo {
+    pub multiplier: u32,
+    pub timer: Timer,
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        let mut timer = Timer::from_seconds(COMBO_WINDOW, false);
+        timer.tick(timer.duration());
+        Combo {
+            multiplier: 1,
+            timer,
+        }
+    }
+}
+
+pub struct ScoreEvent {
+    pub points: u32,
+}
+
+pub fn reset_score_system(mut score: ResMut<Score>, mut combo: ResMut<Combo>) {
+    *score = Score::default();
+    *combo = Combo::default();
+}
+
+pub fn score_system(
+    time: Res<Time>,
+    mut score: ResMut<Score>,
+    mut combo: ResMut<Combo>,
+    mut ev_score: EventReader<ScoreEvent>,
+) {
+    combo.timer.tick(time.delta());
+
+    for ScoreEvent { points } in ev_score.iter() {
+        combo.multiplier = if combo.timer.finished() {
+            1
+        } else {
+            (combo.multiplier + 1).min(MAX_COMBO)
+        };
+        combo.timer.reset();
+
+        score.0 = (score.0 + points * combo.multiplier).min(MAX_SCORE);
+    }
+}
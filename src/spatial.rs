@@ -0,0 +1,215 @@
+use bevy::{prelude::*, utils::StableHashMap};
+
+// roughly the diameter of a medium asteroid
+pub const GRID_CELL_SIZE: f32 = 96.0;
+
+type Cell = (i32, i32);
+
+/**
+ * Shortest offset from a to b when the world wraps around every size units
+ */
+pub fn minimum_image(a: Vec2, b: Vec2, size: Vec2) -> Vec2 {
+    let delta = b - a;
+    delta - size * (delta / size).round()
+}
+
+/**
+ * Broad phase shared by the collision systems. Bodies are bucketed by the cells their
+ * bounding circle covers, so only bodies sharing a cell are ever compared. The grid covers
+ * a torus, bodies reaching over one edge also land in the cells along the opposite edge.
+ */
+#[derive(Debug)]
+pub struct SpatialGrid {
+    origin: Vec2,
+    size: Vec2,
+    // cells across and their actual size, stretched to fit the torus exactly
+    columns: i32,
+    rows: i32,
+    cell_size: Vec2,
+    // stable hashing keeps the order pairs are found in the same between runs
+    cells: StableHashMap<Cell, Vec<Entity>>,
+    bodies: StableHashMap<Entity, (Vec2, f32)>,
+    // every pair of overlapping bounding circles, each pair once
+    pairs: Vec<(Entity, Entity)>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f32, origin: Vec2, size: Vec2) -> Self {
+        let columns = ((size.x / cell_size).floor() as i32).max(1);
+        let rows = ((size.y / cell_size).floor() as i32).max(1);
+        SpatialGrid {
+            origin,
+            size,
+            columns,
+            rows,
+            cell_size: size / Vec2::new(columns as f32, rows as f32),
+            cells: StableHashMap::default(),
+            bodies: StableHashMap::default(),
+            pairs: Vec::new(),
+        }
+    }
+
+    // unwrapped cell coordinates, relative to the origin
+    fn cell(&self, pos: Vec2) -> Cell {
+        let cell = ((pos - self.origin) / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    fn wrap(&self, (x, y): Cell) -> Cell {
+        (x.rem_euclid(self.columns), y.rem_euclid(self.rows))
+    }
+
+    fn covered_cells(&self, pos: Vec2, radius: f32) -> impl Iterator<Item = Cell> + '_ {
+        let (min_x, min_y) = self.cell(pos - Vec2::splat(radius));
+        let (max_x, max_y) = self.cell(pos + Vec2::splat(radius));
+        // a body wider than the torus covers every cell once
+        let max_x = max_x.min(min_x + self.columns - 1);
+        let max_y = max_y.min(min_y + self.rows - 1);
+        (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| self.wrap((x, y))))
+    }
+
+    fn overlapping(&self, (ap, ar): (Vec2, f32), (bp, br): (Vec2, f32)) -> bool {
+        minimum_image(ap, bp, self.size).length_squared() < (ar + br).powi(2)
+    }
+
+    /**
+     * Empties the grid while keeping its allocations around for the next rebuild
+     */
+    pub fn clear(&mut self) {
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+        self.bodies.clear();
+        self.pairs.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, pos: Vec2, radius: f32) {
+        for cell in self.covered_cells(pos, radius).collect::<Vec<_>>() {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+        self.bodies.insert(entity, (pos, radius));
+    }
+
+    /**
+     * Collects the overlapping pairs. A pair sharing several cells is only reported by the
+     * cell holding the corner where their bounding boxes start to overlap.
+     */
+    pub fn build_pairs(&mut self) {
+        self.pairs.clear();
+        for (cell, entities) in self.cells.iter() {
+            for (i, a) in entities.iter().enumerate() {
+                let (ap, ar) = self.bodies[a];
+                for b in entities[i + 1..].iter() {
+                    let (bp, br) = self.bodies[b];
+                    if !self.overlapping((ap, ar), (bp, br)) {
+                        continue;
+                    }
+
+                    // b as seen from a, across the edges if that is closer
+                    let bp = ap + minimum_image(ap, bp, self.size);
+                    let corner = (ap - Vec2::splat(ar)).max(bp - Vec2::splat(br));
+                    if self.wrap(self.cell(corner)) == *cell {
+                        self.pairs.push((*a, *b));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn pairs(&self) -> &[(Entity, Entity)] {
+        &self.pairs
+    }
+
+    /**
+     * Bodies whose bounding circle overlaps the given circle
+     */
+    pub fn query(&self, pos: Vec2, radius: f32) -> Vec<Entity> {
+        let mut found = Vec::new();
+        for cell in self.covered_cells(pos, radius) {
+            let entities = match self.cells.get(&cell) {
+                Some(entities) => entities,
+                None => continue,
+            };
+            for entity in entities {
+                if self.overlapping((pos, radius), self.bodies[entity]) && !found.contains(entity) {
+                    found.push(*entity);
+                }
+            }
+        }
+
+        found
+    }
+
+    /**
+     * Bodies whose bounding circle touches the segment from a to b. The segment is covered by
+     * circles no further apart than a cell, which catch every body within reach of it.
+     */
+    pub fn query_segment(&self, a: Vec2, b: Vec2) -> Vec<Entity> {
+        let step = self.cell_size.min_element();
+        let samples = (a.distance(b) / step).ceil().max(1.0) as usize;
+        let mut found = Vec::new();
+        for i in 0..=samples {
+            let point = a.lerp(b, i as f32 / samples as f32);
+            for entity in self.query(point, step / 2.0) {
+                if !found.contains(&entity) {
+                    found.push(entity);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::vec2;
+
+    // four cells across and three up, each 100 units wide
+    fn grid() -> SpatialGrid {
+        SpatialGrid::new(96.0, Vec2::ZERO, vec2(400.0, 300.0))
+    }
+
+    #[test]
+    fn pairs_are_reported_once_however_many_cells_they_share() {
+        for (a, b, pairs) in [
+            ((vec2(50.0, 50.0), 10.0), (vec2(250.0, 250.0), 10.0), 0),
+            ((vec2(50.0, 50.0), 10.0), (vec2(60.0, 50.0), 10.0), 1),
+            // both cover the same four cells
+            ((vec2(100.0, 100.0), 80.0), (vec2(150.0, 150.0), 80.0), 1),
+            // across the left and right edges
+            ((vec2(5.0, 150.0), 20.0), (vec2(395.0, 150.0), 20.0), 1),
+            // across the corner, sharing cells on all four sides of the torus
+            ((vec2(5.0, 5.0), 20.0), (vec2(395.0, 295.0), 20.0), 1),
+            // reaching over the edge, but not far enough
+            ((vec2(5.0, 150.0), 20.0), (vec2(300.0, 150.0), 20.0), 0),
+            // wider than the torus
+            ((vec2(200.0, 150.0), 500.0), (vec2(50.0, 50.0), 10.0), 1),
+        ] {
+            let mut grid = grid();
+            grid.insert(Entity::from_raw(0), a.0, a.1);
+            grid.insert(Entity::from_raw(1), b.0, b.1);
+            grid.build_pairs();
+            assert_eq!(grid.pairs().len(), pairs, "{a:?} and {b:?}");
+        }
+    }
+
+    #[test]
+    fn segments_find_the_bodies_along_them() {
+        let mut grid = grid();
+        grid.insert(Entity::from_raw(0), vec2(150.0, 55.0), 10.0);
+        grid.insert(Entity::from_raw(1), vec2(350.0, 150.0), 10.0);
+        // across the right edge
+        grid.insert(Entity::from_raw(2), vec2(20.0, 250.0), 10.0);
+
+        assert_eq!(
+            grid.query_segment(vec2(10.0, 50.0), vec2(390.0, 50.0)),
+            vec![Entity::from_raw(0)]
+        );
+        assert_eq!(
+            grid.query_segment(vec2(300.0, 250.0), vec2(430.0, 250.0)),
+            vec![Entity::from_raw(2)]
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use bevy::prelude::*;
+
+use crate::{
+    gui::{draw_text, FontAtlas},
+    random::Seed,
+    FRAME_X_OFFSET, GAME_FRAME_WIDTH,
+};
+
+const OVERLAY_SCALE: f32 = 4.0;
+const OVERLAY_SPACING: f32 = 8.0;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AppState {
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+/**
+ * Marks text that only lives for the duration of a single state
+ */
+#[derive(Debug, Component, Clone)]
+pub struct OverlayText;
+
+pub fn draw_overlay(
+    commands: &mut Commands,
+    atlas: &FontAtlas,
+    lines: &[(&str, f32)],
+    marker: impl Component + Clone,
+) {
+    for (text, y) in lines {
+        // lines too long for the game frame are drawn smaller, in whole steps so the letters
+        // stay crisp
+        let mut scale = OVERLAY_SCALE;
+        while scale > 1.0 && text.len() as f32 * OVERLAY_SPACING * scale > GAME_FRAME_WIDTH {
+            scale -= 1.0;
+        }
+        // center the line in the game frame
+        let width = (text.len() - 1) as f32 * OVERLAY_SPACING * scale;
+        let x = -FRAME_X_OFFSET - width / 2.0;
+        for letter in draw_text(commands, &atlas.0, text, x, *y, scale, OVERLAY_SPACING) {
+            commands.entity(letter).insert(marker.clone());
+        }
+    }
+}
+
+pub fn main_menu_setup_system(mut commands: Commands, atlas: Res<FontAtlas>) {
+    draw_overlay(
+        &mut commands,
+        &atlas,
+        &[("ASTEROIDS", 40.0), ("PRESS SPACE", -40.0)],
+        OverlayText,
+    );
+}
+
+pub fn main_menu_system(mut state: ResMut<State<AppState>>, mut keyboard: ResMut<Input<KeyCode>>) {
+    if keyboard.clear_just_pressed(KeyCode::Space) {
+        let _ = state.set(AppState::Playing);
+    }
+}
+
+pub fn pause_system(mut state: ResMut<State<AppState>>, mut keyboard: ResMut<Input<KeyCode>>) {
+    if keyboard.clear_just_pressed(KeyCode::Escape) || keyboard.clear_just_pressed(KeyCode::P) {
+        let _ = state.push(AppState::Paused);
+    }
+}
+
+pub fn paused_setup_system(mut commands: Commands, atlas: Res<FontAtlas>) {
+    draw_overlay(&mut commands, &atlas, &[("PAUSED", 0.0)], OverlayText);
+}
+
+pub fn resume_system(mut state: ResMut<State<AppState>>, mut keyboard: ResMut<Input<KeyCode>>) {
+    if keyboard.clear_just_pressed(KeyCode::Escape) || keyboard.clear_just_pressed(KeyCode::P) {
+        let _ = state.pop();
+    }
+}
+
+pub fn game_over_setup_system(mut commands: Commands, atlas: Res<FontAtlas>, seed: Res<Seed>) {
+    // --seed plays the same game again
+    println!("Game over, seed {}", seed.value());
+    draw_overlay(
+        &mut commands,
+        &atlas,
+        &[
+            ("GAME OVER", 60.0),
+            (&format!("SEED {}", seed.value()), 0.0),
+            ("PRESS SPACE", -60.0),
+        ],
+        OverlayText,
+    );
+}
+
+pub fn game_over_system(mut state: ResMut<State<AppState>>, mut keyboard: ResMut<Input<KeyCode>>) {
+    if keyboard.clear_just_pressed(KeyCode::Space) {
+        let _ = state.set(AppState::MainMenu);
+    }
+}
+
+/**
+ * Despawns every entity with the component T. Used to clean up when leaving a state.
+ */
+pub fn despawn_system<T: Component>(mut commands: Commands, query: Query<Entity, With<T>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
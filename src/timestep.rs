@@ -0,0 +1,246 @@
+use std::time::Duration;
+
+use bevy::{
+    ecs::schedule::{IntoSystemDescriptor, StageLabel},
+    prelude::*,
+    utils::Instant,
+};
+
+use crate::{
+    boundary::wrapped_delta,
+    movement::{AngularVelocity, Velocity},
+    state::AppState,
+};
+
+// the simulation always advances in steps of this size, whatever the frame rate
+pub const TIME_STEP: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// a slow frame catches up at most this many steps, the rest of the backlog is dropped
+const MAX_STEPS_PER_FRAME: u32 = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, StageLabel)]
+pub struct FixedUpdate;
+
+/**
+ * Progress of the fixed timestep simulation
+ */
+#[derive(Debug, Default)]
+pub struct SimulationClock {
+    // steps simulated since startup
+    pub tick: u64,
+    // how far the rendered frame is between the last two steps
+    pub alpha: f32,
+}
+
+/**
+ * The transforms of a moving body at the last two steps. What gets rendered is a blend of the
+ * two, so motion stays smooth when the frame rate and the step rate don't line up.
+ */
+#[derive(Debug, Component, Clone, Copy)]
+pub struct Interpolated {
+    previous: Transform,
+    current: Transform,
+}
+
+impl Interpolated {
+    fn new(transform: Transform) -> Self {
+        Interpolated {
+            previous: transform,
+            current: transform,
+        }
+    }
+
+    /**
+     * Jumps straight to transform, for bodies that are teleported rather than moved
+     */
+    pub fn snap(&mut self, transform: Transform) {
+        self.previous = transform;
+        self.current = transform;
+    }
+
+    fn blend(&self, alpha: f32) -> Transform {
+        // the body may have wrapped during the last step, blend across the edge in that case
+        let delta = wrapped_delta(
+            self.previous.translation.truncate(),
+            self.current.translation.truncate(),
+        );
+        let translation = self.current.translation - (delta * (1.0 - alpha)).extend(0.0);
+
+        Transform {
+            translation,
+            rotation: self.previous.rotation.slerp(self.current.rotation, alpha),
+            scale: self.current.scale,
+        }
+    }
+}
+
+/**
+ * Runs the gameplay systems at a fixed rate while the game is being played. During a step
+ * the Time resource is swapped for one that advances by exactly TIME_STEP, so every system
+ * reading it sees the same deltas on every machine.
+ */
+pub struct FixedTimestepStage {
+    step: Duration,
+    accumulator: Duration,
+    // step once per run, however much time has passed
+    lockstep: bool,
+    time: Time,
+    stage: SystemStage,
+}
+
+impl FixedTimestepStage {
+    pub fn new(step: Duration, stage: SystemStage) -> Self {
+        let mut time = Time::default();
+        time.update_with_instant(time.startup());
+        FixedTimestepStage {
+            step,
+            accumulator: Duration::ZERO,
+            lockstep: false,
+            time,
+            stage,
+        }
+    }
+
+    /**
+     * Runs exactly one step every frame, for running the simulation as fast as it goes
+     * without a display
+     */
+    pub fn lockstep(mut self) -> Self {
+        self.lockstep = true;
+        self
+    }
+
+    fn playing(world: &World) -> bool {
+        // without a state machine the game is always being played
+        match world.get_resource::<State<AppState>>() {
+            Some(state) => *state.current() == AppState::Playing,
+            None => true,
+        }
+    }
+
+    /**
+     * Runs a single step of the simulation
+     */
+    pub fn step(&mut self, world: &mut World) {
+        let last_update = self.time.last_update().unwrap_or_else(Instant::now);
+        self.time.update_with_instant(last_update + self.step);
+
+        let frame_time = world.remove_resource::<Time>();
+        world.insert_resource(self.time.clone());
+        track_new_bodies(world);
+        let mut bodies = world.query::<(&Transform, &mut Interpolated)>();
+        for (transform, mut interpolated) in bodies.iter_mut(world) {
+            interpolated.previous = *transform;
+        }
+
+        self.stage.run(world);
+
+        world.resource_mut::<SimulationClock>().tick += 1;
+        if let Some(time) = frame_time {
+            world.insert_resource(time);
+        }
+    }
+}
+
+impl Stage for FixedTimestepStage {
+    fn run(&mut self, world: &mut World) {
+        if !Self::playing(world) {
+            // don't make up for the time spent in menus
+            self.accumulator = Duration::ZERO;
+            return;
+        }
+
+        restore_simulated(world);
+
+        if self.lockstep {
+            self.accumulator = self.step;
+        } else {
+            self.accumulator += world.resource::<Time>().delta();
+        }
+        let mut steps = 0;
+        while self.accumulator >= self.step && Self::playing(world) {
+            if steps == MAX_STEPS_PER_FRAME {
+                self.accumulator = Duration::ZERO;
+                break;
+            }
+
+            self.accumulator -= self.step;
+            steps += 1;
+            self.step(world);
+        }
+
+        let alpha = match self.lockstep {
+            true => 1.0,
+            false => self.accumulator.as_secs_f32() / self.step.as_secs_f32(),
+        };
+        world.resource_mut::<SimulationClock>().alpha = alpha;
+        interpolate(world, alpha);
+    }
+}
+
+/**
+ * Adds systems to the fixed timestep stage, which has to be in the app already
+ */
+pub trait SimulationApp {
+    fn add_simulation_system<Params>(
+        &mut self,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self;
+    fn add_simulation_system_set(&mut self, set: SystemSet) -> &mut Self;
+}
+
+impl SimulationApp for App {
+    fn add_simulation_system<Params>(
+        &mut self,
+        system: impl IntoSystemDescriptor<Params>,
+    ) -> &mut Self {
+        self.stage(FixedUpdate, |stage: &mut FixedTimestepStage| {
+            stage.stage.add_system(system);
+            stage
+        })
+    }
+
+    fn add_simulation_system_set(&mut self, set: SystemSet) -> &mut Self {
+        self.stage(FixedUpdate, |stage: &mut FixedTimestepStage| {
+            stage.stage.add_system_set(set);
+            stage
+        })
+    }
+}
+
+/**
+ * Puts the simulated transforms back before stepping
+ */
+fn restore_simulated(world: &mut World) {
+    let mut bodies = world.query::<(&mut Transform, &Interpolated)>();
+    for (mut transform, interpolated) in bodies.iter_mut(world) {
+        *transform = interpolated.current;
+    }
+}
+
+/**
+ * Starts interpolating bodies that began moving. Done on every step rather than every frame,
+ * since adding the component changes the order queries visit bodies in.
+ */
+fn track_new_bodies(world: &mut World) {
+    let mut untracked = world.query_filtered::<(Entity, &Transform), (
+        Or<(With<Velocity>, With<AngularVelocity>)>,
+        Without<Interpolated>,
+    )>();
+    let untracked: Vec<(Entity, Transform)> = untracked
+        .iter(world)
+        .map(|(entity, transform)| (entity, *transform))
+        .collect();
+    for (entity, transform) in untracked {
+        world
+            .entity_mut(entity)
+            .insert(Interpolated::new(transform));
+    }
+}
+
+fn interpolate(world: &mut World, alpha: f32) {
+    let mut bodies = world.query::<(&mut Transform, &mut Interpolated)>();
+    for (mut transform, mut interpolated) in bodies.iter_mut(world) {
+        interpolated.current = *transform;
+        *transform = interpolated.blend(alpha);
+    }
+}
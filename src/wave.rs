@@ -0,0 +1,178 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::{asteroid::Asteroid, gui::FontAtlas, state::draw_overlay};
+
+// in seconds
+const BANNER_DURATION: f32 = 2.5;
+// per stage past the last hand made one
+const BUDGET_INCREMENT: u32 = 2;
+const SPEED_INCREMENT: f32 = 0.1;
+const MAX_SPEED_FACTOR: f32 = 2.5;
+
+#[derive(Debug, Clone)]
+pub struct StageConfig {
+    // asteroids spawned by the director, not counting fragments
+    pub budget: u32,
+    // relative weights of large, medium and small asteroids
+    pub size_mix: [u32; 3],
+    // multiplier applied to the base asteroid velocity
+    pub speed: Range<f32>,
+    // in seconds
+    pub spawn_interval: f32,
+}
+
+const STAGES: [StageConfig; 5] = [
+    StageConfig {
+        budget: 4,
+        size_mix: [4, 1, 0],
+        speed: 0.8..1.0,
+        spawn_interval: 2.0,
+    },
+    StageConfig {
+        budget: 6,
+        size_mix: [3, 2, 1],
+        speed: 0.9..1.1,
+        spawn_interval: 1.75,
+    },
+    StageConfig {
+        budget: 8,
+        size_mix: [3, 3, 2],
+        speed: 1.0..1.2,
+        spawn_interval: 1.5,
+    },
+    StageConfig {
+        budget: 10,
+        size_mix: [2, 3, 3],
+        speed: 1.1..1.4,
+        spawn_interval: 1.25,
+    },
+    StageConfig {
+        budget: 12,
+        size_mix: [2, 3, 4],
+        speed: 1.2..1.6,
+        spawn_interval: 1.0,
+    },
+];
+
+impl StageConfig {
+    /**
+     * Stages past the hand made ones repeat the last one with a bigger budget and more speed
+     */
+    pub fn for_stage(stage: u32) -> Self {
+        let index = (stage.max(1) - 1) as usize;
+        if let Some(config) = STAGES.get(index) {
+            return config.clone();
+        }
+
+        let last = &STAGES[STAGES.len() - 1];
+        let extra = (index - (STAGES.len() - 1)) as u32;
+        // the whole range moves up, so it keeps its width once the top reaches the cap
+        let speed_bonus = (extra as f32 * SPEED_INCREMENT).min(MAX_SPEED_FACTOR - last.speed.end);
+        StageConfig {
+            budget: last.budget + extra * BUDGET_INCREMENT,
+            size_mix: last.size_mix,
+            speed: last.speed.start + speed_bonus..last.speed.end + speed_bonus,
+            spawn_interval: last.spawn_interval,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CurrentStage(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WavePhase {
+    Banner,
+    Spawning,
+    Clearing,
+}
+
+#[derive(Debug)]
+pub struct WaveDirector {
+    pub phase: WavePhase,
+    pub config: StageConfig,
+    pub remaining: u32,
+    pub timer: Timer,
+}
+
+impl WaveDirector {
+    pub fn new(stage: u32) -> Self {
+        let config = StageConfig::for_stage(stage);
+        WaveDirector {
+            phase: WavePhase::Banner,
+            remaining: config.budget,
+            config,
+            timer: Timer::from_seconds(BANNER_DURATION, false),
+        }
+    }
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        WaveDirector::new(1)
+    }
+}
+
+#[derive(Debug, Component, Clone)]
+pub struct StageBanner;
+
+fn show_banner(commands: &mut Commands, atlas: &FontAtlas, stage: u32) {
+    draw_overlay(
+        commands,
+        atlas,
+        &[(&format!("STAGE {}", stage), 0.0)],
+        StageBanner,
+    );
+}
+
+pub fn reset_wave_system(
+    mut commands: Commands,
+    atlas: Res<FontAtlas>,
+    mut stage: ResMut<CurrentStage>,
+    mut director: ResMut<WaveDirector>,
+) {
+    *stage = CurrentStage(1);
+    *director = WaveDirector::new(stage.0);
+    show_banner(&mut commands, &atlas, stage.0);
+}
+
+/**
+ * Moves the wave from its banner, through spawning, to cleared and on to the next stage.
+ * The spawning itself happens in asteroid_spawn_system.
+ */
+pub fn wave_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    atlas: Res<FontAtlas>,
+    mut stage: ResMut<CurrentStage>,
+    mut director: ResMut<WaveDirector>,
+    banners: Query<Entity, With<StageBanner>>,
+    asteroids: Query<(), With<Asteroid>>,
+) {
+    match director.phase {
+        WavePhase::Banner => {
+            if director.timer.tick(time.delta()).just_finished() {
+                for banner in banners.iter() {
+                    commands.entity(banner).despawn();
+                }
+                let spawn_interval = director.config.spawn_interval;
+                director.phase = WavePhase::Spawning;
+                director.timer = Timer::from_seconds(spawn_interval, true);
+            }
+        }
+        WavePhase::Spawning => {
+            if director.remaining == 0 {
+                director.phase = WavePhase::Clearing;
+            }
+        }
+        WavePhase::Clearing => {
+            if asteroids.is_empty() {
+                stage.0 += 1;
+                *director = WaveDirector::new(stage.0);
+                show_banner(&mut commands, &atlas, stage.0);
+            }
+        }
+    }
+}
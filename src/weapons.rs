@@ -1,74 +1,919 @@
-use std::time::Duration;
-
-use crate::asteroid::Damage;
+use crate::{
+    asteroid::{Asteroid, AsteroidSize, AsteroidSplitEvent, Damage, Health, Points},
+    boundary::{wrapped_delta, wrapped_segments, FRAME_SIZE},
+    collision::{contact, spatial_grid_system},
+    controls::ControlInput,
+    geometry::{closest_point_on_segment, segment_polygon_intersection, world_polygon},
+    movement::{AngularVelocity, Drive, DriveMode, Mass, SpeedLimit},
+    particles::GrainParticleSpawnEvent,
+    random::{Random, WeaponSpread},
+    score::ScoreEvent,
+    spatial::SpatialGrid,
+    state::{despawn_system, AppState},
+    timestep::SimulationApp,
+    DestructionEvent, GameSystem, Ship,
+};
 
-use super::{BoundaryRemoval, Bounding, Velocity, LIGHT, POLY_LINE_WIDTH};
-use bevy::{
-    math::{vec2, vec3},
-    prelude::*,
+use super::{
+    BoundaryRemoval, BoundaryWrap, Bounding, TimedRemoval, Velocity, LIGHT, POLY_LINE_WIDTH,
 };
+use bevy::{ecs::system::SystemParam, math::vec2, prelude::*, utils::HashSet};
 use bevy_prototype_lyon::{
     entity::ShapeBundle,
-    prelude::{DrawMode, FillMode, GeometryBuilder, StrokeMode},
+    prelude::{DrawMode, FillMode, GeometryBuilder, Path, PathBuilder, StrokeMode},
     shapes,
 };
-use derive_more::From;
+use rand::Rng;
+use serde::Deserialize;
 
 pub const CANNON_BULLET_RADIUS: f32 = 1.0;
 
+/**
+ * The ship's weapons and the bullets they fire
+ */
+pub struct WeaponsPlugin;
+
+impl Plugin for WeaponsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_simulation_system_set(
+            SystemSet::new()
+                .label(GameSystem::Input)
+                .with_system(weapon_cycle_system)
+                .with_system(weapon_control_system.after(weapon_cycle_system))
+                .with_system(homing_system),
+        )
+        .add_simulation_system_set(
+            SystemSet::new()
+                .label(GameSystem::Boundary)
+                .with_system(bullet_despawn_system)
+                .after(GameSystem::Movement),
+        )
+        // the beam hits what is there after moving, and reports it like the other collisions
+        .add_simulation_system(
+            beam_system
+                .label(GameSystem::Collision)
+                .after(GameSystem::Boundary)
+                .after(spatial_grid_system),
+        )
+        .add_simulation_system(
+            missile_impact_system
+                .label(GameSystem::Collision)
+                .after(GameSystem::Boundary)
+                .after(spatial_grid_system),
+        )
+        .add_system_set(
+            SystemSet::on_exit(AppState::Playing)
+                .with_system(despawn_system::<Bullet>)
+                .with_system(despawn_system::<Beam>),
+        );
+    }
+}
+
 #[derive(Debug, Component)]
 pub struct Bullet(pub Timer);
 
-#[derive(Debug, Component, Default, Deref, DerefMut, From)]
-pub struct Cannon(pub f32);
+/**
+ * What a weapon fires and how fast. The ship carries one in each slot of its loadout, in the
+ * order of the config.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weapon {
+    // shown in the stat frame, letters and digits only
+    pub name: String,
+    pub damage: f32,
+    pub speed: f32,
+    // in seconds
+    pub lifetime: f32,
+    // shots per second
+    pub fire_rate: f32,
+    // bullets stray up to this many degrees to either side of the nose
+    pub spread: f32,
+    // added to the heat gauge with every shot, a full gauge is 1
+    pub heat: f32,
+    // mass per unit of area
+    pub density: f32,
+    pub projectile: Projectile,
+    pub pattern: Pattern,
+}
+
+impl Default for Weapon {
+    fn default() -> Self {
+        Weapon {
+            name: "CANNON".to_string(),
+            damage: 10.0,
+            speed: 400.0,
+            lifetime: 1.25,
+            fire_rate: 8.0,
+            spread: 0.0,
+            heat: 0.06,
+            density: 1.0,
+            projectile: Projectile::Round {
+                radius: CANNON_BULLET_RADIUS,
+            },
+            pattern: Pattern::default(),
+        }
+    }
+}
+
+impl Weapon {
+    /**
+     * Checks the stats, reasons start with the name of the offending field
+     */
+    pub fn validate(&self) -> Result<(), String> {
+        // fits next to the shield readout
+        if self.name.is_empty()
+            || self.name.len() > 6
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        {
+            return Err(format!(
+                "name must be 1 to 6 capital letters or digits, got {:?}",
+                self.name
+            ));
+        }
+
+        let mut stats = vec![("damage", self.damage), ("density", self.density)];
+        // a beam has nothing in flight and no shots to space out
+        if !matches!(self.projectile, Projectile::Beam { .. }) {
+            stats.extend([
+                ("speed", self.speed),
+                ("lifetime", self.lifetime),
+                ("fire_rate", self.fire_rate),
+            ]);
+        }
+        for (name, value) in stats {
+            if value <= 0.0 {
+                return Err(format!("{name} must be greater than 0, got {value}"));
+            }
+        }
+
+        let (name, size) = match self.projectile {
+            Projectile::Round { radius } => ("radius", radius),
+            Projectile::Bolt { length } | Projectile::Missile { length, .. } => ("length", length),
+            Projectile::Beam { range } => ("range", range),
+        };
+        if size <= 0.0 {
+            return Err(format!(
+                "projectile.{name} must be greater than 0, got {size}"
+            ));
+        }
+        if let Projectile::Beam { range } = self.projectile {
+            // the ray is only tested against the asteroids next to the frame
+            let longest = FRAME_SIZE.min_element();
+            if range > longest {
+                return Err(format!(
+                    "projectile.range can't reach further than {longest}, got {range}"
+                ));
+            }
+        }
+
+        if self.heat < 0.0 {
+            return Err(format!("heat must be at least 0, got {}", self.heat));
+        }
+        if !(0.0..180.0).contains(&self.spread) {
+            return Err(format!("spread must be in [0, 180), got {}", self.spread));
+        }
+        if let Projectile::Missile {
+            thrust,
+            turn_rate,
+            lock_cone,
+            lock_range,
+            blast_radius,
+            ..
+        } = self.projectile
+        {
+            for (name, value) in [
+                ("thrust", thrust),
+                ("turn_rate", turn_rate),
+                ("lock_range", lock_range),
+                ("blast_radius", blast_radius),
+            ] {
+                if value <= 0.0 {
+                    return Err(format!(
+                        "projectile.{name} must be greater than 0, got {value}"
+                    ));
+                }
+            }
+            if !(0.0..=180.0).contains(&lock_cone) {
+                return Err(format!(
+                    "projectile.lock_cone must be in [0, 180], got {lock_cone}"
+                ));
+            }
+        }
+
+        self.pattern
+            .validate()
+            .map_err(|reason| format!("pattern.{reason}"))
+    }
+}
+
+/**
+ * How the bullets of a shot leave the ship. The default fires a single bullet from the nose.
+ */
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Pattern {
+    // bullets per shot, fanned out evenly over the arc
+    pub count: u32,
+    // in degrees
+    pub arc: f32,
+    // shots fired by one pull of the trigger
+    pub burst: u32,
+    // between the shots of a burst, in seconds
+    pub burst_interval: f32,
+    // shots alternate between muzzles this far to the left and right of the nose, as a fraction
+    // of the ship's radius
+    pub muzzle_offset: f32,
+}
+
+impl Default for Pattern {
+    fn default() -> Self {
+        Pattern {
+            count: 1,
+            arc: 0.0,
+            burst: 1,
+            burst_interval: 0.05,
+            muzzle_offset: 0.0,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, value) in [("count", self.count), ("burst", self.burst)] {
+            if value == 0 {
+                return Err(format!("{name} must be at least 1"));
+            }
+        }
+        if !(0.0..360.0).contains(&self.arc) {
+            return Err(format!("arc must be in [0, 360), got {}", self.arc));
+        }
+        if self.burst_interval <= 0.0 {
+            return Err(format!(
+                "burst_interval must be greater than 0, got {}",
+                self.burst_interval
+            ));
+        }
+        if self.muzzle_offset < 0.0 {
+            return Err(format!(
+                "muzzle_offset must be at least 0, got {}",
+                self.muzzle_offset
+            ));
+        }
+
+        Ok(())
+    }
+
+    /**
+     * Angles of the bullets of one shot off the nose, in degrees
+     */
+    pub fn angles(&self) -> impl Iterator<Item = f32> + '_ {
+        let step = match self.count {
+            1 => 0.0,
+            count => self.arc / (count - 1) as f32,
+        };
+        (0..self.count).map(move |i| -self.arc / 2.0 + step * i as f32)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub enum Projectile {
+    Round {
+        radius: f32,
+    },
+    // a streak pointing where it flies
+    Bolt {
+        length: f32,
+    },
+    // a continuous ray out of the nose for as long as the trigger is held. Damage and heat are
+    // per second, the fire rate and pattern don't apply
+    Beam {
+        range: f32,
+    },
+    // flies off at half the speed and picks up the rest with its own drive, turning towards the
+    // nearest asteroid in front of it. Blows up on contact, the damage reaches every asteroid
+    // within the blast radius
+    Missile {
+        length: f32,
+        // added to the speed per step at the reference rate, like the ship's drive
+        thrust: f32,
+        // in degrees per second
+        turn_rate: f32,
+        // asteroids up to this many degrees to either side of the nose can be locked on to
+        lock_cone: f32,
+        lock_range: f32,
+        blast_radius: f32,
+    },
+}
+
+impl Projectile {
+    pub fn radius(&self) -> f32 {
+        match self {
+            Projectile::Round { radius } => *radius,
+            Projectile::Bolt { length } | Projectile::Missile { length, .. } => length / 2.0,
+            // nothing flies
+            Projectile::Beam { .. } => 0.0,
+        }
+    }
+
+    fn shape(&self, direction: Vec2, translation: Vec3) -> ShapeBundle {
+        let transform = Transform::from_translation(translation);
+        match self {
+            Projectile::Round { radius } => GeometryBuilder::build_as(
+                &shapes::Circle {
+                    radius: *radius,
+                    ..Default::default()
+                },
+                DrawMode::Outlined {
+                    outline_mode: StrokeMode::new(LIGHT, POLY_LINE_WIDTH),
+                    fill_mode: FillMode::color(LIGHT),
+                },
+                transform,
+            ),
+            Projectile::Bolt { length } => GeometryBuilder::build_as(
+                &shapes::Line(direction * -*length / 2.0, direction * *length / 2.0),
+                DrawMode::Stroke(StrokeMode::new(LIGHT, POLY_LINE_WIDTH)),
+                transform,
+            ),
+            // turns with the transform as it steers
+            Projectile::Missile { length, .. } => GeometryBuilder::build_as(
+                &shapes::Line(vec2(0.0, *length / 2.0), vec2(0.0, -*length / 2.0)),
+                DrawMode::Stroke(StrokeMode::new(LIGHT, POLY_LINE_WIDTH)),
+                transform.with_rotation(Quat::from_rotation_arc_2d(-Vec2::Y, direction)),
+            ),
+            Projectile::Beam { .. } => unreachable!("beams are drawn by beam_system"),
+        }
+    }
+}
+
+/**
+ * The weapons the ship carries and the one it fires
+ */
+#[derive(Debug, Component)]
+pub struct Loadout {
+    pub slots: Vec<Weapon>,
+    pub current: usize,
+    // runs down between shots of the current weapon
+    pub cooldown: Timer,
+    // shots of the current burst still to come and the time until the next one
+    pub burst_left: u32,
+    pub burst_timer: Timer,
+    // 1 or -1, flips to the other side of the nose with every shot
+    pub muzzle: f32,
+}
+
+impl Loadout {
+    /**
+     * A loadout needs a weapon to select, without any it carries the default one
+     */
+    pub fn new(slots: Vec<Weapon>) -> Self {
+        let slots = match slots.is_empty() {
+            true => vec![Weapon::default()],
+            false => slots,
+        };
+        Loadout {
+            slots,
+            current: 0,
+            cooldown: Timer::from_seconds(0.0, false),
+            burst_left: 0,
+            burst_timer: Timer::from_seconds(0.0, false),
+            muzzle: 1.0,
+        }
+    }
+
+    pub fn weapon(&self) -> &Weapon {
+        &self.slots[self.current]
+    }
+
+    pub fn cycle(&mut self) {
+        self.current = (self.current + 1) % self.slots.len();
+        // the rest of a burst belongs to the weapon that started it
+        self.burst_left = 0;
+    }
+
+    /**
+     * Swaps in new weapons, keeping the selected slot where there still is one. An empty list
+     * keeps the weapons the ship has.
+     */
+    pub fn reload(&mut self, slots: Vec<Weapon>) {
+        if slots.is_empty() {
+            return;
+        }
+        self.current = self.current.min(slots.len() - 1);
+        self.slots = slots;
+    }
+}
+
+/**
+ * Builds up as the ship fires. A full gauge locks the weapons until it has cooled down
+ * completely.
+ */
+#[derive(Debug, Component)]
+pub struct Heat {
+    pub level: f32,
+    // fraction of the gauge shed per second
+    pub cooling: f32,
+    pub overheated: bool,
+}
+
+impl Heat {
+    pub fn new(cooling: f32) -> Self {
+        Heat {
+            level: 0.0,
+            cooling,
+            overheated: false,
+        }
+    }
+
+    pub fn cool(&mut self, delta: f32) {
+        self.level = (self.level - self.cooling * delta).max(0.0);
+        if self.level == 0.0 {
+            self.overheated = false;
+        }
+    }
+
+    pub fn add(&mut self, heat: f32) {
+        self.level = (self.level + heat).min(1.0);
+        if self.level == 1.0 {
+            self.overheated = true;
+        }
+    }
+}
+
+pub fn weapon_cycle_system(mut query: Query<&mut Loadout>, mut input: ResMut<ControlInput>) {
+    // spent like a fire press when there is nothing to cycle
+    if !std::mem::take(&mut input.cycle_weapon) {
+        return;
+    }
+
+    for mut loadout in query.iter_mut() {
+        loadout.cycle();
+    }
+}
 
-pub fn cannon_control_system(
+pub fn weapon_control_system(
     mut commands: Commands,
-    query: Query<(&Transform, &Bounding, &Cannon)>,
-    keyboard: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    mut query: Query<(&Transform, &Bounding, &mut Loadout, &mut Heat)>,
+    mut input: ResMut<ControlInput>,
+    mut rng: Local<Random<WeaponSpread>>,
 ) {
-    for (transform, bounding, cannon) in query.iter() {
-        if keyboard.just_pressed(KeyCode::Space) {
-            let direction = transform.rotation * -Vec3::Y; //TODO: find out why this works
-            let shape = shapes::Circle {
-                radius: CANNON_BULLET_RADIUS,
-                ..Default::default()
+    // the press is spent even when there is no weapon to fire it, or it has to cool down
+    let fire = std::mem::take(&mut input.fire) || input.fire_held;
+    for (transform, bounding, mut loadout, mut heat) in query.iter_mut() {
+        loadout.cooldown.tick(time.delta());
+        loadout.burst_timer.tick(time.delta());
+        // only touched when there is something to cool, so the gauge is redrawn when it moves
+        if heat.level > 0.0 {
+            heat.cool(time.delta_seconds());
+        }
+        if heat.overheated {
+            loadout.burst_left = 0;
+            continue;
+        }
+
+        let weapon = loadout.weapon().clone();
+        if let Projectile::Beam { .. } = weapon.projectile {
+            continue;
+        }
+        if loadout.burst_left > 0 {
+            if !loadout.burst_timer.finished() {
+                continue;
+            }
+            loadout.burst_left -= 1;
+        } else if fire && loadout.cooldown.finished() {
+            loadout.cooldown = Timer::from_seconds(1.0 / weapon.fire_rate, false);
+            loadout.burst_left = weapon.pattern.burst - 1;
+        } else {
+            continue;
+        }
+        loadout.burst_timer = Timer::from_seconds(weapon.pattern.burst_interval, false);
+        heat.add(weapon.heat);
+
+        let muzzle = loadout.muzzle * weapon.pattern.muzzle_offset * bounding.0;
+        if weapon.pattern.muzzle_offset > 0.0 {
+            loadout.muzzle = -loadout.muzzle;
+        }
+        for angle in weapon.pattern.angles() {
+            let stray = rng.gen_range(-weapon.spread..=weapon.spread);
+            let rotation = transform.rotation * Quat::from_rotation_z((angle + stray).to_radians());
+            let direction = (rotation * -Vec3::Y).truncate(); //TODO: find out why this works
+            let nose = (transform.rotation * -Vec3::Y).truncate();
+            // the muzzles sit to either side of the nose
+            let position =
+                transform.translation.truncate() + nose * bounding.0 + nose.perp() * muzzle;
+
+            match weapon.projectile {
+                Projectile::Missile { .. } => commands
+                    .spawn()
+                    .insert_bundle(MissileBundle::new(&weapon, position, direction)),
+                _ => commands
+                    .spawn()
+                    .insert_bundle(BulletBundle::new(&weapon, position, direction)),
             };
+        }
+    }
+}
+
+/**
+ * The line drawn for the beam a ship is firing
+ */
+#[derive(Debug, Component)]
+pub struct Beam {
+    pub of: Entity,
+}
+
+type Targets<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static Transform,
+        &'static Bounding,
+        &'static Points,
+        &'static mut Health,
+        &'static Velocity,
+        &'static AsteroidSize,
+    ),
+    With<Asteroid>,
+>;
+
+// the first asteroid outline a beam crosses
+struct BeamHit {
+    asteroid: Entity,
+    // where the beam is cut off, past the frame edges when it wraps
+    end: Vec2,
+    // the same point on the asteroid itself
+    point: Vec2,
+}
+
+fn cast_beam(origin: Vec2, end: Vec2, grid: &SpatialGrid, asteroids: &Targets) -> Option<BeamHit> {
+    let mut closest: Option<BeamHit> = None;
+    for (asteroid, transform, bounding, points, health, _, _) in
+        asteroids.iter_many(grid.query_segment(origin, end))
+    {
+        if health.0 < 0.0 {
+            continue;
+        }
+        // the asteroid and its copies a frame away, in case the beam wraps
+        for x in -1..=1 {
+            for y in -1..=1 {
+                let shift = vec2(x as f32, y as f32) * FRAME_SIZE;
+                let center = transform.translation.truncate() + shift;
+                if closest_point_on_segment(center, origin, end).distance(center) > bounding.0 {
+                    continue;
+                }
 
-            let _bullet = commands.spawn().insert_bundle(BulletBundle {
-                bounding: Bounding::from(CANNON_BULLET_RADIUS),
-                velocity: Velocity::from(vec2(cannon.0 * direction.x, cannon.0 * direction.y)),
-                bullet: Bullet(Timer::new(Duration::from_millis(1250), false)),
-                boundary_removal: BoundaryRemoval,
-                damage: Damage(10.0),
-                shape: (GeometryBuilder::build_as(
-                    &shape,
-                    DrawMode::Outlined {
-                        outline_mode: StrokeMode::new(LIGHT, POLY_LINE_WIDTH),
-                        fill_mode: FillMode::color(LIGHT),
-                    },
-                    Transform {
-                        translation: transform.translation
-                            + vec3(direction.x * bounding.0, direction.y * bounding.0, 0.0),
-                        ..Default::default()
-                    },
-                )),
-            });
-            // .insert(Bounding::from(CANNON_BULLET_RADIUS))
-            // .insert(BoundaryRemoval)
-            // .insert(Velocity::from(vec2(
-            //     cannon.0 * direction.x,
-            //     cannon.0 * direction.y,
-            // )))
-            // .insert(Bullet(Timer::new(Duration::from_millis(1250), false)));
+                let mut copy = *transform;
+                copy.translation += shift.extend(0.0);
+                let polygon = world_polygon(&points.0, &copy);
+                let hit = match segment_polygon_intersection(origin, end, &polygon) {
+                    Some(hit) => hit,
+                    None => continue,
+                };
+                let nearer = match &closest {
+                    Some(closest) => hit.distance(origin) < closest.end.distance(origin),
+                    None => true,
+                };
+                if nearer {
+                    closest = Some(BeamHit {
+                        asteroid,
+                        end: hit,
+                        point: hit - shift,
+                    });
+                }
+            }
+        }
+    }
+
+    closest
+}
+
+/**
+ * Takes weapon damage off asteroids, and breaks up the ones it destroys the way bullets do
+ */
+#[derive(SystemParam)]
+pub struct AsteroidHits<'w, 's> {
+    ev_grain: EventWriter<'w, 's, GrainParticleSpawnEvent>,
+    ev_destruction: EventWriter<'w, 's, DestructionEvent>,
+    ev_asteroid_split: EventWriter<'w, 's, AsteroidSplitEvent>,
+    ev_score: EventWriter<'w, 's, ScoreEvent>,
+}
+
+impl<'w, 's> AsteroidHits<'w, 's> {
+    /**
+     * Hits asteroid at impact, going along direction. True when that destroyed it.
+     */
+    fn deal(
+        &mut self,
+        asteroids: &mut Targets,
+        asteroid: Entity,
+        damage: f32,
+        impact: Vec2,
+        direction: Vec2,
+    ) -> bool {
+        let (asteroid, at, ab, points, mut health, av, size) = match asteroids.get_mut(asteroid) {
+            Ok(target) => target,
+            Err(_) => return false,
+        };
+        // already destroyed earlier in the step
+        if health.0 < 0.0 {
+            return false;
+        }
+        health.0 -= damage;
+        if health.0 >= 0.0 {
+            return false;
+        }
+
+        self.ev_destruction
+            .send(DestructionEvent { entity: asteroid });
+        self.ev_score.send(ScoreEvent {
+            points: size.score(),
+        });
+        match size.fragments() {
+            Some(fragments) => {
+                self.ev_asteroid_split.send(AsteroidSplitEvent {
+                    parent_points: points.0.clone(),
+                    parent_transform: *at,
+                    parent_velocity: av.0,
+                    impact,
+                    direction,
+                    size: fragments,
+                    amount: 3,
+                });
+                self.ev_grain.send(GrainParticleSpawnEvent {
+                    pos: at.translation,
+                    spawn_radius: ab.0 / 1.5,
+                    particles: 100..200,
+                    impact_vel: vec2(0.0, 0.0),
+                });
+            }
+            None => {
+                self.ev_grain.send(GrainParticleSpawnEvent {
+                    pos: impact.extend(1.0),
+                    spawn_radius: ab.0,
+                    particles: 30..70,
+                    impact_vel: direction * 30.0,
+                });
+            }
+        }
+
+        true
+    }
+}
+
+/**
+ * Fires the beam weapons whose trigger is held. Every step the first asteroid along the beam
+ * takes the damage and the beam is drawn up to where it hits.
+ */
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn beam_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    input: Res<ControlInput>,
+    mut ships: Query<(Entity, &Transform, &Bounding, &Loadout, &mut Heat), With<Ship>>,
+    mut asteroids: Targets,
+    grid: Res<SpatialGrid>,
+    mut beams: Query<(Entity, &Beam, &mut Path)>,
+    mut hits: AsteroidHits,
+) {
+    let mut firing = HashSet::new();
+    for (ship, transform, bounding, loadout, mut heat) in ships.iter_mut() {
+        let weapon = loadout.weapon();
+        let range = match weapon.projectile {
+            Projectile::Beam { range } => range,
+            _ => continue,
+        };
+        if !input.fire_held || heat.overheated {
+            continue;
+        }
+        heat.add(weapon.heat * time.delta_seconds());
+        firing.insert(ship);
+
+        let direction = (transform.rotation * -Vec3::Y).truncate();
+        let origin = transform.translation.truncate() + direction * bounding.0;
+        let mut end = origin + direction * range;
+        if let Some(hit) = cast_beam(origin, end, &grid, &asteroids) {
+            end = hit.end;
+            let damage = weapon.damage * time.delta_seconds();
+            if !hits.deal(&mut asteroids, hit.asteroid, damage, hit.point, direction) {
+                // sparks thrown back at the ship
+                hits.ev_grain.send(GrainParticleSpawnEvent {
+                    pos: hit.point.extend(1.0),
+                    spawn_radius: 2.0,
+                    particles: 1..4,
+                    impact_vel: -direction * 60.0,
+                });
+            }
+        }
+
+        let mut builder = PathBuilder::new();
+        for (a, b) in wrapped_segments(origin, end) {
+            builder.move_to(a);
+            builder.line_to(b);
+        }
+        let path = builder.build();
+        match beams.iter_mut().find(|(_, beam, _)| beam.of == ship) {
+            Some((_, _, mut beam_path)) => *beam_path = path,
+            None => {
+                commands
+                    .spawn_bundle(ShapeBundle {
+                        path,
+                        mode: DrawMode::Stroke(StrokeMode::new(LIGHT, POLY_LINE_WIDTH)),
+                        transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                        ..default()
+                    })
+                    .insert(Beam { of: ship });
+            }
+        }
+    }
+
+    // let go of the trigger, switched weapons, overheated or died
+    for (entity, beam, _) in beams.iter() {
+        if !firing.contains(&beam.of) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/**
+ * A homing missile in flight and the asteroid it is locked on to
+ */
+#[derive(Debug, Component)]
+pub struct Missile {
+    pub target: Option<Entity>,
+    // in radians per second
+    pub turn_rate: f32,
+    // in radians to either side of the nose
+    pub lock_cone: f32,
+    pub lock_range: f32,
+    pub blast_radius: f32,
+}
+
+/**
+ * Keeps each missile locked on to an asteroid in front of it, looking for the nearest one when
+ * it has none, and turns it towards the target as fast as it can
+ */
+pub fn homing_system(
+    time: Res<Time>,
+    mut missiles: Query<(&Transform, &mut AngularVelocity, &mut Missile)>,
+    asteroids: Query<(Entity, &Transform, &Health), With<Asteroid>>,
+) {
+    for (transform, mut angular_velocity, mut missile) in missiles.iter_mut() {
+        let position = transform.translation.truncate();
+        let nose = (transform.rotation * -Vec3::Y).truncate();
+        // the lock holds until the asteroid is destroyed, wherever it goes
+        let locked = missile
+            .target
+            .and_then(|target| asteroids.get(target).ok())
+            .filter(|(_, _, health)| health.0 >= 0.0);
+        // towards the target across the frame edges when that is closer
+        let target = match locked {
+            Some((_, target, _)) => Some(wrapped_delta(position, target.translation.truncate())),
+            None => {
+                let nearest = asteroids
+                    .iter()
+                    .filter(|(_, _, health)| health.0 >= 0.0)
+                    .map(|(asteroid, at, _)| {
+                        (asteroid, wrapped_delta(position, at.translation.truncate()))
+                    })
+                    .filter(|(_, delta)| {
+                        delta.length() <= missile.lock_range
+                            && nose.angle_between(*delta).abs() <= missile.lock_cone
+                    })
+                    .min_by(|(_, a), (_, b)| a.length_squared().total_cmp(&b.length_squared()));
+                missile.target = nearest.map(|(asteroid, _)| asteroid);
+                nearest.map(|(_, delta)| delta)
+            }
+        };
+
+        angular_velocity.0 = match target {
+            Some(target) => {
+                // the turn that would face the target within this step
+                let turn = nose.angle_between(target) / time.delta_seconds();
+                turn.clamp(-missile.turn_rate, missile.turn_rate)
+            }
+            None => 0.0,
+        };
+    }
+}
+
+/**
+ * Blows up missiles touching an asteroid. Every asteroid the blast reaches takes the missile's
+ * damage.
+ */
+#[allow(clippy::type_complexity)]
+pub fn missile_impact_system(
+    mut commands: Commands,
+    missiles: Query<(Entity, &Transform, &Bounding, &Velocity, &Damage, &Missile)>,
+    mut asteroids: Targets,
+    grid: Res<SpatialGrid>,
+    mut hits: AsteroidHits,
+) {
+    for (entity, transform, bounding, velocity, damage, missile) in missiles.iter() {
+        let position = transform.translation.truncate();
+        let struck = asteroids.iter_many(grid.query(position, bounding.0)).any(
+            |(_, at, ab, points, health, _, _)| {
+                health.0 >= 0.0
+                    && contact(transform, bounding, None, at, ab, Some(points)).is_some()
+            },
+        );
+        if !struck {
+            continue;
+        }
+
+        commands.entity(entity).despawn();
+        let blast = transform.translation.truncate();
+        let direction = velocity.0.normalize_or_zero();
+        hits.ev_grain.send(GrainParticleSpawnEvent {
+            pos: blast.extend(1.0),
+            spawn_radius: missile.blast_radius / 2.0,
+            particles: 40..80,
+            impact_vel: vec2(0.0, 0.0),
+        });
+
+        // the asteroid's outline is inside its bounding circle
+        let caught: Vec<(Entity, Vec2)> = asteroids
+            .iter_many(grid.query(blast, missile.blast_radius))
+            .map(|(asteroid, at, ..)| (asteroid, wrapped_delta(blast, at.translation.truncate())))
+            .collect();
+        for (asteroid, delta) in caught {
+            // blown outwards from the blast
+            let direction = delta.try_normalize().unwrap_or(direction);
+            hits.deal(&mut asteroids, asteroid, damage.0, blast, direction);
         }
     }
 }
 
 #[derive(Bundle)]
+struct MissileBundle {
+    missile: Missile,
+    bounding: Bounding,
+    // chases targets across the frame edges
+    wrap: BoundaryWrap,
+    removal: TimedRemoval,
+    velocity: Velocity,
+    angular_velocity: AngularVelocity,
+    drive: Drive,
+    speed_limit: SpeedLimit,
+    damage: Damage,
+    #[bundle]
+    shape: ShapeBundle,
+}
+
+impl MissileBundle {
+    /**
+     * A missile of weapon leaving a muzzle at position
+     */
+    fn new(weapon: &Weapon, position: Vec2, direction: Vec2) -> Self {
+        let (thrust, turn_rate, lock_cone, lock_range, blast_radius) = match weapon.projectile {
+            Projectile::Missile {
+                thrust,
+                turn_rate,
+                lock_cone,
+                lock_range,
+                blast_radius,
+                ..
+            } => (thrust, turn_rate, lock_cone, lock_range, blast_radius),
+            _ => unreachable!("only missile weapons launch missiles"),
+        };
+        let radius = weapon.projectile.radius();
 
+        MissileBundle {
+            missile: Missile {
+                target: None,
+                turn_rate: turn_rate.to_radians(),
+                lock_cone: lock_cone.to_radians(),
+                lock_range,
+                blast_radius,
+            },
+            bounding: Bounding::from(radius),
+            wrap: BoundaryWrap,
+            removal: TimedRemoval(Timer::from_seconds(weapon.lifetime, false)),
+            velocity: Velocity::from(direction * weapon.speed / 2.0),
+            angular_velocity: AngularVelocity::default(),
+            // always burning, it leaves the same exhaust as the ship
+            drive: Drive {
+                mode: DriveMode::Propulsion,
+                propulsion_force: thrust,
+                reverse_force: 0.0,
+            },
+            speed_limit: SpeedLimit::from(weapon.speed),
+            damage: Damage(weapon.damage),
+            shape: weapon
+                .projectile
+                .shape(direction, (position + direction * radius).extend(0.0)),
+        }
+    }
+}
+
+#[derive(Bundle)]
 struct BulletBundle {
     bounding: Bounding,
+    mass: Mass,
     boundary_removal: BoundaryRemoval,
     velocity: Velocity,
     damage: Damage,
@@ -77,6 +922,32 @@ struct BulletBundle {
     shape: ShapeBundle,
 }
 
+impl BulletBundle {
+    /**
+     * A bullet of weapon leaving a muzzle at position
+     */
+    fn new(weapon: &Weapon, position: Vec2, direction: Vec2) -> Self {
+        let radius = weapon.projectile.radius();
+        // bolts start at the muzzle, round bullets are centered on it
+        let offset = match weapon.projectile {
+            Projectile::Bolt { .. } => radius,
+            _ => 0.0,
+        };
+
+        BulletBundle {
+            bounding: Bounding::from(radius),
+            mass: Mass::disc(radius, weapon.density),
+            velocity: Velocity::from(direction * weapon.speed),
+            bullet: Bullet(Timer::from_seconds(weapon.lifetime, false)),
+            boundary_removal: BoundaryRemoval,
+            damage: Damage(weapon.damage),
+            shape: weapon
+                .projectile
+                .shape(direction, (position + direction * offset).extend(0.0)),
+        }
+    }
+}
+
 pub fn bullet_despawn_system(
     time: Res<Time>,
     mut commands: Commands,
@@ -0,0 +1,85 @@
+use bevy::{math::vec2, prelude::*};
+
+use crate::Harness;
+use asteroids_bevy::{
+    boundary::{boundary_wrapping_system, wrapped_segments, BoundaryWrap},
+    movement::{movement_system, Velocity},
+    FRAME_END_X, FRAME_END_Y, FRAME_START_X, FRAME_START_Y,
+};
+
+fn wrapping() -> Harness {
+    Harness::new(
+        SystemStage::single_threaded()
+            .with_system(movement_system)
+            .with_system(boundary_wrapping_system.after(movement_system)),
+    )
+}
+
+#[test]
+fn bodies_leaving_the_frame_come_back_on_the_opposite_side() {
+    let mut harness = wrapping();
+    // two units per step
+    let right = harness.spawn((
+        Transform::from_xyz(FRAME_END_X - 1.0, 0.0, 0.0),
+        Velocity(vec2(120.0, 0.0)),
+        BoundaryWrap,
+    ));
+    let down = harness.spawn((
+        Transform::from_xyz(0.0, FRAME_START_Y + 1.0, 0.0),
+        Velocity(vec2(0.0, -120.0)),
+        BoundaryWrap,
+    ));
+
+    harness.step(1);
+
+    let right = harness.get::<Transform>(right).unwrap().translation;
+    assert!((right.x - (FRAME_START_X + 1.0)).abs() < 0.01, "{right}");
+    assert_eq!(right.y, 0.0);
+    let down = harness.get::<Transform>(down).unwrap().translation;
+    assert!((down.y - (FRAME_END_Y - 1.0)).abs() < 0.01, "{down}");
+}
+
+#[test]
+fn bodies_inside_the_frame_are_left_alone() {
+    let mut harness = wrapping();
+    let body = harness.spawn((
+        Transform::from_xyz(FRAME_END_X - 10.0, 0.0, 0.0),
+        Velocity(vec2(120.0, 0.0)),
+        BoundaryWrap,
+    ));
+
+    harness.step(1);
+
+    let x = harness.get::<Transform>(body).unwrap().translation.x;
+    assert!((x - (FRAME_END_X - 8.0)).abs() < 0.01, "{x}");
+}
+
+#[test]
+fn only_wrapping_bodies_wrap() {
+    let mut harness = wrapping();
+    let body = harness.spawn((
+        Transform::from_xyz(FRAME_END_X - 1.0, 0.0, 0.0),
+        Velocity(vec2(120.0, 0.0)),
+    ));
+
+    harness.step(1);
+
+    assert!(harness.get::<Transform>(body).unwrap().translation.x > FRAME_END_X);
+}
+
+#[test]
+fn segments_crossing_an_edge_continue_on_the_opposite_side() {
+    let segments = wrapped_segments(vec2(FRAME_END_X - 10.0, 0.0), vec2(FRAME_END_X + 30.0, 0.0));
+
+    assert_eq!(
+        segments,
+        vec![
+            (vec2(FRAME_END_X - 10.0, 0.0), vec2(FRAME_END_X, 0.0)),
+            (vec2(FRAME_START_X, 0.0), vec2(FRAME_START_X + 30.0, 0.0)),
+        ]
+    );
+    assert_eq!(
+        wrapped_segments(Vec2::ZERO, Vec2::ONE),
+        vec![(Vec2::ZERO, Vec2::ONE)]
+    );
+}
@@ -0,0 +1,140 @@
+use bevy::{math::vec2, prelude::*};
+
+use crate::{asteroid, Harness};
+use asteroids_bevy::{
+    asteroid::{asteroid_split_system, Asteroid, AsteroidSize, Damage, Health},
+    collision::{damage_transfer_system, spatial_grid_system, Bounding},
+    destruction_system,
+    geometry::polygon_polygon_contact,
+    movement::{Mass, Velocity},
+    weapons::{Bullet, CANNON_BULLET_RADIUS},
+};
+
+const ASTEROID_RADIUS: f32 = 40.0;
+
+fn hits() -> Harness {
+    Harness::new(
+        SystemStage::single_threaded()
+            .with_system(spatial_grid_system)
+            .with_system(damage_transfer_system::<Bullet, Asteroid>.after(spatial_grid_system))
+            .with_system(destruction_system.after(damage_transfer_system::<Bullet, Asteroid>))
+            .with_system(asteroid_split_system.after(damage_transfer_system::<Bullet, Asteroid>)),
+    )
+}
+
+// a bullet just inside the asteroid's right edge, flying into it
+fn bullet(harness: &mut Harness, damage: f32) -> Entity {
+    harness.spawn((
+        Bullet(Timer::from_seconds(1.0, false)),
+        Damage(damage),
+        Transform::from_xyz(ASTEROID_RADIUS - 2.0, 0.0, 0.0),
+        Velocity(vec2(-400.0, 0.0)),
+        Bounding::from(CANNON_BULLET_RADIUS),
+        Mass::disc(CANNON_BULLET_RADIUS, 1.0),
+    ))
+}
+
+#[test]
+fn bullets_take_their_damage_off_the_asteroid_health() {
+    let mut harness = hits();
+    let asteroid = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ASTEROID_RADIUS,
+        Vec2::ZERO,
+    );
+    let bullet = bullet(&mut harness, 10.0);
+
+    harness.step(1);
+
+    assert_eq!(harness.get::<Health>(asteroid).unwrap().0, 20.0);
+    assert!(!harness.exists(bullet), "the bullet should be spent");
+}
+
+#[test]
+fn bullets_missing_the_asteroid_do_no_damage() {
+    let mut harness = hits();
+    let asteroid = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ASTEROID_RADIUS,
+        Vec2::ZERO,
+    );
+    let bullet = harness.spawn((
+        Bullet(Timer::from_seconds(1.0, false)),
+        Damage(10.0),
+        Transform::from_xyz(ASTEROID_RADIUS * 3.0, 0.0, 0.0),
+        Velocity::default(),
+        Bounding::from(CANNON_BULLET_RADIUS),
+    ));
+
+    harness.step(1);
+
+    assert_eq!(harness.get::<Health>(asteroid).unwrap().0, 30.0);
+    assert!(harness.exists(bullet));
+}
+
+#[test]
+fn destroyed_asteroids_split_into_the_next_size_class() {
+    for (size, fragments) in [
+        (AsteroidSize::Large, AsteroidSize::Medium),
+        (AsteroidSize::Medium, AsteroidSize::Small),
+    ] {
+        let mut harness = hits();
+        let parent = asteroid(&mut harness, size, ASTEROID_RADIUS, Vec2::ZERO);
+        bullet(&mut harness, 100.0);
+
+        harness.step(1);
+
+        assert!(!harness.exists(parent), "{size:?} should be destroyed");
+        assert_eq!(harness.count::<AsteroidSize>(|s| *s == size), 0);
+        assert_eq!(
+            harness.count::<AsteroidSize>(|s| *s == fragments),
+            3,
+            "{size:?} should split into {fragments:?} pieces"
+        );
+    }
+}
+
+#[test]
+fn small_asteroids_do_not_split() {
+    let mut harness = hits();
+    let parent = asteroid(
+        &mut harness,
+        AsteroidSize::Small,
+        ASTEROID_RADIUS,
+        Vec2::ZERO,
+    );
+    bullet(&mut harness, 100.0);
+
+    harness.step(1);
+
+    assert!(!harness.exists(parent));
+    assert_eq!(harness.count::<Asteroid>(|_| true), 0);
+}
+
+#[test]
+fn outlines_crossing_only_at_their_edges_still_overlap() {
+    // a cross, no corner of either bar is inside the other
+    let wide = [
+        vec2(-10.0, -2.0),
+        vec2(10.0, -2.0),
+        vec2(10.0, 2.0),
+        vec2(-10.0, 2.0),
+    ];
+    let tall = [
+        vec2(1.0, -10.0),
+        vec2(5.0, -10.0),
+        vec2(5.0, 10.0),
+        vec2(1.0, 10.0),
+    ];
+
+    let contact = polygon_polygon_contact(&wide, &tall).unwrap();
+
+    assert_eq!(contact.normal, Vec2::X);
+    assert!(
+        (contact.depth - 9.0).abs() < 1e-4,
+        "depth {}",
+        contact.depth
+    );
+}
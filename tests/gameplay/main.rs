@@ -0,0 +1,135 @@
+use std::f32::consts::TAU;
+
+use bevy::{ecs::event::Event, prelude::*};
+
+use asteroids_bevy::{
+    asteroid::{Asteroid, AsteroidSize, AsteroidSpawnEvent, AsteroidSplitEvent, Points},
+    boundary::{FRAME_ORIGIN, FRAME_SIZE},
+    collision::Bounding,
+    config::GameConfig,
+    controls::ControlInput,
+    lives::Lives,
+    movement::{Mass, Velocity},
+    particles::{BallParticleSpawnEvent, GrainParticleSpawnEvent},
+    random::Seed,
+    score::{Combo, Score, ScoreEvent},
+    spatial::{SpatialGrid, GRID_CELL_SIZE},
+    state::AppState,
+    timestep::{FixedTimestepStage, FixedUpdate, SimulationClock, TIME_STEP},
+    wave::CurrentStage,
+    Debug, DestructionEvent, PlayerDeathEvent,
+};
+
+mod boundary;
+mod collision;
+mod replay;
+mod ship;
+mod wave;
+mod weapons;
+
+/**
+ * A bare App running only the systems a test is about, stepped one fixed simulation step per
+ * update the way the game runs them
+ */
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    pub fn new(stage: SystemStage) -> Self {
+        let mut app = App::new();
+        app.insert_resource(GameConfig::default())
+            .insert_resource(Debug(false))
+            .insert_resource(Seed::new(Some(0)))
+            .insert_resource(State::new(AppState::Playing))
+            .insert_resource(SpatialGrid::new(GRID_CELL_SIZE, FRAME_ORIGIN, FRAME_SIZE))
+            .init_resource::<Lives>()
+            .init_resource::<Score>()
+            .init_resource::<Combo>()
+            .init_resource::<CurrentStage>()
+            .init_resource::<ControlInput>()
+            .init_resource::<SimulationClock>()
+            .add_event::<AsteroidSpawnEvent>()
+            .add_event::<AsteroidSplitEvent>()
+            .add_event::<DestructionEvent>()
+            .add_event::<PlayerDeathEvent>()
+            .add_event::<ScoreEvent>()
+            .add_event::<GrainParticleSpawnEvent>()
+            .add_event::<BallParticleSpawnEvent>()
+            .add_stage_before(
+                CoreStage::Update,
+                FixedUpdate,
+                FixedTimestepStage::new(TIME_STEP, stage).lockstep(),
+            );
+
+        Harness { app }
+    }
+
+    pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+        self.app.world.spawn().insert_bundle(bundle).id()
+    }
+
+    pub fn send<E: Event>(&mut self, event: E) {
+        self.app.world.send_event(event);
+    }
+
+    pub fn step(&mut self, steps: u32) {
+        for _ in 0..steps {
+            self.app.update();
+        }
+    }
+
+    /**
+     * Steps until done holds, returns how many steps that took
+     */
+    pub fn step_until(&mut self, max_steps: u32, done: impl Fn(&World) -> bool) -> u32 {
+        for steps in 1..=max_steps {
+            self.app.update();
+            if done(&self.app.world) {
+                return steps;
+            }
+        }
+
+        panic!("still waiting after {max_steps} steps");
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.app.world.get::<T>(entity)
+    }
+
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.app.world.get_entity(entity).is_some()
+    }
+
+    pub fn count<T: Component>(&mut self, matching: impl Fn(&T) -> bool) -> usize {
+        let mut query = self.app.world.query::<&T>();
+        query.iter(&self.app.world).filter(|c| matching(c)).count()
+    }
+}
+
+/**
+ * Steps needed for the given number of seconds to pass
+ */
+pub fn steps(seconds: f32) -> u32 {
+    (seconds / TIME_STEP.as_secs_f32()).ceil() as u32
+}
+
+/**
+ * A resting twelve-sided asteroid of the given size class, with its corners on a circle of
+ * radius around position
+ */
+pub fn asteroid(harness: &mut Harness, size: AsteroidSize, radius: f32, position: Vec2) -> Entity {
+    let points: Vec<Vec2> = (0..12)
+        .map(|i| Vec2::from_angle(i as f32 / 12.0 * TAU) * radius)
+        .collect();
+    harness.spawn((
+        Asteroid,
+        size,
+        size.health(),
+        Transform::from_translation(position.extend(0.0)),
+        Velocity::default(),
+        Bounding::from(radius),
+        Mass::polygon(&points, 1.0),
+        Points(points),
+    ))
+}
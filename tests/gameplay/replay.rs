@@ -0,0 +1,28 @@
+use asteroids_bevy::{controls::ControlInput, replay::Replay};
+
+#[test]
+fn replays_round_trip_through_their_encoding() {
+    let mut replay = Replay::new(42);
+    let fire = ControlInput {
+        fire: true,
+        ..Default::default()
+    };
+    replay.steps = vec![ControlInput::default(), fire, fire, ControlInput::default()];
+
+    let decoded = Replay::decode(&replay.encode()).unwrap();
+
+    assert_eq!(decoded.seed, 42);
+    assert_eq!(decoded.steps.len(), 4);
+    assert!(decoded.steps[1].fire && decoded.steps[2].fire);
+}
+
+#[test]
+fn runs_past_the_step_count_are_rejected() {
+    let mut bytes = Replay::new(42).encode();
+    // claims a single step, then brings a run of the longest kind
+    bytes[13..17].copy_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    bytes.extend_from_slice(&u16::MAX.to_le_bytes());
+
+    assert!(Replay::decode(&bytes).is_err());
+}
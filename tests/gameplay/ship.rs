@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+
+use crate::{steps, Harness};
+use asteroids_bevy::{
+    hull::Hull,
+    lives::{Lives, STARTING_LIVES},
+    movement::{drive_system, speed_limit_system, Drive, DriveMode, SpeedLimit, Velocity},
+    player_state_system,
+    weapons::Loadout,
+    PlayerDeathEvent, Ship, ShipState,
+};
+
+fn state(world: &World, ship: Entity) -> ShipState {
+    world.get::<Ship>(ship).unwrap().state.clone()
+}
+
+#[test]
+fn destroyed_ships_die_respawn_and_come_back_alive() {
+    let mut harness = Harness::new(SystemStage::single_threaded().with_system(player_state_system));
+    let ship = harness.spawn((
+        Ship {
+            state: ShipState::Alive,
+            timer: Timer::from_seconds(0.0, false),
+        },
+        Transform::from_xyz(100.0, 50.0, 0.0),
+        Visibility::default(),
+        Velocity::default(),
+    ));
+
+    harness.send(PlayerDeathEvent {});
+    harness.step(1);
+
+    assert!(matches!(state(&harness.app.world, ship), ShipState::Dead));
+    assert!(!harness.get::<Visibility>(ship).unwrap().is_visible);
+    assert!(
+        harness.get::<Velocity>(ship).is_none(),
+        "dead ships don't move"
+    );
+    assert_eq!(
+        harness.app.world.resource::<Lives>().remaining,
+        STARTING_LIVES - 1
+    );
+
+    let dead_for = harness.step_until(steps(3.0), |world| {
+        matches!(state(world, ship), ShipState::Spawning)
+    });
+    assert_eq!(dead_for, steps(2.0));
+    assert_eq!(
+        harness.get::<Transform>(ship).unwrap().translation,
+        Vec3::ZERO
+    );
+    assert!(harness.get::<Visibility>(ship).unwrap().is_visible);
+    assert!(
+        harness.get::<Loadout>(ship).is_none(),
+        "no shooting while spawning"
+    );
+
+    let spawning_for = harness.step_until(steps(2.0), |world| {
+        matches!(state(world, ship), ShipState::Alive)
+    });
+    assert_eq!(spawning_for, steps(1.0));
+    assert!(harness.get::<Velocity>(ship).is_some());
+    assert!(harness.get::<Loadout>(ship).is_some());
+    assert!(harness.get::<Hull>(ship).is_some());
+}
+
+#[test]
+fn ships_only_lose_one_life_per_step() {
+    let mut harness = Harness::new(SystemStage::single_threaded().with_system(player_state_system));
+    harness.spawn((
+        Ship {
+            state: ShipState::Alive,
+            timer: Timer::from_seconds(0.0, false),
+        },
+        Transform::default(),
+        Visibility::default(),
+    ));
+
+    harness.send(PlayerDeathEvent {});
+    harness.send(PlayerDeathEvent {});
+    harness.step(1);
+
+    assert_eq!(
+        harness.app.world.resource::<Lives>().remaining,
+        STARTING_LIVES - 1
+    );
+}
+
+#[test]
+fn engines_push_no_faster_than_the_speed_limit() {
+    let mut harness = Harness::new(
+        SystemStage::single_threaded()
+            .with_system(drive_system)
+            .with_system(speed_limit_system.after(drive_system)),
+    );
+    let ship = harness.spawn((
+        Transform::default(),
+        Velocity::default(),
+        Drive {
+            mode: DriveMode::Propulsion,
+            propulsion_force: 3.0,
+            reverse_force: 0.0,
+        },
+        SpeedLimit(200.0),
+    ));
+
+    harness.step(steps(5.0));
+
+    let speed = harness.get::<Velocity>(ship).unwrap().length();
+    assert!((speed - 200.0).abs() < 1e-3, "speed {speed}");
+}
@@ -0,0 +1,18 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use asteroids_bevy::wave::StageConfig;
+
+#[test]
+fn late_stages_keep_a_speed_range_to_pick_from() {
+    let mut rng = SmallRng::seed_from_u64(0);
+    for stage in [5, 6, 18, 50, 1000] {
+        let config = StageConfig::for_stage(stage);
+        assert!(
+            !config.speed.is_empty(),
+            "stage {stage} has no speeds, {:?}",
+            config.speed
+        );
+        assert!(config.speed.end <= 2.5, "stage {stage} is too fast");
+        rng.gen_range(config.speed);
+    }
+}
@@ -0,0 +1,441 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{math::vec2, prelude::*};
+
+use crate::{asteroid, steps, Harness};
+use asteroids_bevy::{
+    asteroid::{AsteroidSize, Damage, Health},
+    boundary::{boundary_wrapping_system, BoundaryWrap},
+    collision::{spatial_grid_system, Bounding},
+    config::GameConfig,
+    controls::ControlInput,
+    movement::{
+        drive_system, movement_system, speed_limit_system, AngularVelocity, Drive, DriveMode,
+        SpeedLimit, Velocity,
+    },
+    weapons::{
+        beam_system, bullet_despawn_system, homing_system, missile_impact_system,
+        weapon_control_system, weapon_cycle_system, Beam, Bullet, Heat, Loadout, Missile, Pattern,
+        Projectile, Weapon,
+    },
+    Ship, ShipState, FRAME_END_X, FRAME_START_X,
+};
+
+fn armed() -> (Harness, Entity) {
+    armed_with(GameConfig::default().weapons)
+}
+
+fn armed_with(slots: Vec<Weapon>) -> (Harness, Entity) {
+    let mut harness = Harness::new(
+        SystemStage::single_threaded()
+            .with_system(weapon_cycle_system)
+            .with_system(weapon_control_system.after(weapon_cycle_system)),
+    );
+    let config = GameConfig::default();
+    let ship = harness.spawn((
+        Transform::default(),
+        Bounding::from(10.0),
+        Loadout::new(slots),
+        Heat::new(config.ship.cooling),
+    ));
+
+    (harness, ship)
+}
+
+fn press(harness: &mut Harness, input: ControlInput) {
+    *harness.app.world.resource_mut::<ControlInput>() = input;
+    harness.step(1);
+}
+
+#[test]
+fn bullets_expire_after_their_lifetime() {
+    let mut harness =
+        Harness::new(SystemStage::single_threaded().with_system(bullet_despawn_system));
+    let bullet = harness.spawn((Bullet(Timer::from_seconds(0.5, false)),));
+
+    harness.step(steps(0.5) - 1);
+    assert!(harness.exists(bullet), "expired early");
+
+    harness.step(1);
+    assert!(!harness.exists(bullet), "still around after its lifetime");
+}
+
+#[test]
+fn weapons_fire_no_faster_than_their_fire_rate() {
+    let (mut harness, ship) = armed();
+    let fire_rate = harness.get::<Loadout>(ship).unwrap().weapon().fire_rate;
+
+    // a second of mashing the trigger
+    for _ in 0..steps(1.0) {
+        press(
+            &mut harness,
+            ControlInput {
+                fire: true,
+                ..default()
+            },
+        );
+    }
+
+    assert_eq!(harness.count::<Bullet>(|_| true), fire_rate as usize);
+}
+
+#[test]
+fn holding_the_trigger_fires_until_the_weapons_overheat() {
+    let (mut harness, ship) = armed();
+    *harness.app.world.resource_mut::<ControlInput>() = ControlInput {
+        fire_held: true,
+        ..default()
+    };
+
+    let overheated = |world: &World| world.get::<Heat>(ship).unwrap().overheated;
+    harness.step_until(steps(60.0), overheated);
+    let fired = harness.count::<Bullet>(|_| true);
+    assert!(fired > 1, "auto fire stopped after {fired} shots");
+
+    // the first shot after the lockout comes in the step the gauge runs empty
+    harness.step_until(steps(60.0), |world| !overheated(world));
+    assert_eq!(harness.count::<Bullet>(|_| true), fired + 1);
+}
+
+#[test]
+fn cycling_goes_through_every_slot_and_wraps_around() {
+    let (mut harness, ship) = armed();
+    let slots = harness.get::<Loadout>(ship).unwrap().slots.len();
+    let cycle = ControlInput {
+        cycle_weapon: true,
+        ..default()
+    };
+
+    for slot in 1..=slots {
+        press(&mut harness, cycle);
+        assert_eq!(harness.get::<Loadout>(ship).unwrap().current, slot % slots);
+    }
+}
+
+#[test]
+fn loadouts_never_end_up_without_a_weapon() {
+    let mut loadout = Loadout::new(vec![]);
+    assert_eq!(loadout.weapon().name, Weapon::default().name);
+
+    let slots = GameConfig::default().weapons;
+    loadout.reload(slots.clone());
+    loadout.cycle();
+    loadout.reload(vec![]);
+    assert_eq!(loadout.slots.len(), slots.len(), "the old weapons are kept");
+    assert_eq!(loadout.weapon().name, slots[1].name);
+}
+
+#[test]
+fn spread_patterns_fan_the_bullets_out_over_the_arc() {
+    let (mut harness, _) = armed_with(vec![Weapon {
+        pattern: Pattern {
+            count: 5,
+            arc: 40.0,
+            ..default()
+        },
+        ..default()
+    }]);
+
+    press(
+        &mut harness,
+        ControlInput {
+            fire: true,
+            ..default()
+        },
+    );
+
+    let mut angles: Vec<f32> = harness
+        .app
+        .world
+        .query_filtered::<&Velocity, With<Bullet>>()
+        .iter(&harness.app.world)
+        .map(|velocity| (-Vec2::Y).angle_between(velocity.0).to_degrees())
+        .collect();
+    angles.sort_by(f32::total_cmp);
+    for (angle, expected) in angles.iter().zip([-20.0, -10.0, 0.0, 10.0, 20.0]) {
+        assert!((angle - expected).abs() < 0.01, "{angles:?}");
+    }
+    assert_eq!(angles.len(), 5);
+}
+
+#[test]
+fn bursts_fire_from_alternating_muzzles_on_one_pull() {
+    let (mut harness, _) = armed_with(vec![Weapon {
+        pattern: Pattern {
+            burst: 4,
+            burst_interval: 0.05,
+            muzzle_offset: 0.5,
+            ..default()
+        },
+        ..default()
+    }]);
+
+    press(
+        &mut harness,
+        ControlInput {
+            fire: true,
+            ..default()
+        },
+    );
+    assert_eq!(harness.count::<Bullet>(|_| true), 1);
+    // the rest follow without touching the trigger again
+    harness.step(steps(0.05 * 4.0));
+
+    let mut sides: Vec<(f32, f32)> = harness
+        .app
+        .world
+        .query::<(&Bullet, &Transform)>()
+        .iter(&harness.app.world)
+        // the oldest bullet has the least time left
+        .map(|(bullet, transform)| (bullet.0.percent_left(), transform.translation.x))
+        .collect();
+    assert_eq!(sides.len(), 4, "one pull fires the whole burst");
+    sides.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for pair in sides.windows(2) {
+        assert!(
+            pair[0].1 * pair[1].1 < 0.0,
+            "shots should alternate sides: {sides:?}"
+        );
+    }
+}
+
+const ROCK_RADIUS: f32 = 20.0;
+
+// a ship at x pointing to the right, holding down the trigger of a laser
+fn laser(x: f32) -> Harness {
+    let mut harness = Harness::new(
+        SystemStage::single_threaded()
+            .with_system(spatial_grid_system)
+            .with_system(beam_system.after(spatial_grid_system)),
+    );
+    harness.spawn((
+        Ship {
+            state: ShipState::Alive,
+            timer: Timer::from_seconds(0.0, false),
+        },
+        Transform::from_xyz(x, 0.0, 0.0).with_rotation(Quat::from_rotation_z(FRAC_PI_2)),
+        Bounding::from(10.0),
+        Loadout::new(vec![Weapon {
+            projectile: Projectile::Beam { range: 300.0 },
+            ..default()
+        }]),
+        Heat::new(0.35),
+    ));
+    *harness.app.world.resource_mut::<ControlInput>() = ControlInput {
+        fire_held: true,
+        ..default()
+    };
+
+    harness
+}
+
+#[test]
+fn beams_only_hurt_the_first_asteroid_in_their_way() {
+    let mut harness = laser(-200.0);
+    let near = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(-100.0, 0.0),
+    );
+    let far = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(0.0, 0.0),
+    );
+
+    harness.step(1);
+
+    let full = AsteroidSize::Large.health().0;
+    assert!(harness.get::<Health>(near).unwrap().0 < full);
+    assert_eq!(harness.get::<Health>(far).unwrap().0, full);
+
+    harness.step(1);
+    assert_eq!(harness.count::<Beam>(|_| true), 1);
+    *harness.app.world.resource_mut::<ControlInput>() = ControlInput::default();
+    harness.step(1);
+    assert_eq!(
+        harness.count::<Beam>(|_| true),
+        0,
+        "the beam stays after letting go"
+    );
+}
+
+#[test]
+fn beams_reach_across_the_frame_edges() {
+    let mut harness = laser(FRAME_END_X - 20.0);
+    let across = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(FRAME_START_X + 60.0, 0.0),
+    );
+
+    harness.step(1);
+
+    assert!(harness.get::<Health>(across).unwrap().0 < AsteroidSize::Large.health().0);
+}
+
+#[test]
+fn missiles_lock_on_to_the_nearest_asteroid_in_front_and_turn_towards_it() {
+    let mut harness = Harness::new(
+        SystemStage::single_threaded()
+            .with_system(homing_system)
+            .with_system(movement_system.after(homing_system)),
+    );
+    // pointing to the right
+    let missile = harness.spawn((
+        Missile {
+            target: None,
+            turn_rate: 180_f32.to_radians(),
+            lock_cone: 30_f32.to_radians(),
+            lock_range: 300.0,
+            blast_radius: 40.0,
+        },
+        Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_2)),
+        AngularVelocity::default(),
+    ));
+    let ahead = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(150.0, 50.0),
+    );
+    asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(60.0, -80.0),
+    );
+    asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(-50.0, 0.0),
+    );
+
+    harness.step(1);
+    assert_eq!(harness.get::<Missile>(missile).unwrap().target, Some(ahead));
+
+    harness.step(steps(0.5));
+    let nose = (harness.get::<Transform>(missile).unwrap().rotation * -Vec3::Y).truncate();
+    assert!(
+        nose.angle_between(vec2(150.0, 50.0)).abs() < 0.01,
+        "the missile doesn't face its target, nose {nose}"
+    );
+}
+
+#[test]
+fn missiles_chase_their_target_across_the_frame_edges() {
+    let mut harness = Harness::new(
+        SystemStage::single_threaded()
+            .with_system(homing_system)
+            .with_system(drive_system.after(homing_system))
+            .with_system(speed_limit_system.after(drive_system))
+            .with_system(movement_system.after(speed_limit_system))
+            .with_system(boundary_wrapping_system.after(movement_system))
+            .with_system(spatial_grid_system.after(boundary_wrapping_system))
+            .with_system(missile_impact_system.after(spatial_grid_system)),
+    );
+    // pointing to the right, at the right edge
+    let missile = harness.spawn((
+        Missile {
+            target: None,
+            turn_rate: 180_f32.to_radians(),
+            lock_cone: 30_f32.to_radians(),
+            lock_range: 300.0,
+            blast_radius: 40.0,
+        },
+        Transform::from_xyz(FRAME_END_X - 20.0, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_z(FRAC_PI_2)),
+        Velocity(vec2(150.0, 0.0)),
+        AngularVelocity::default(),
+        Drive {
+            mode: DriveMode::Propulsion,
+            propulsion_force: 6.0,
+            reverse_force: 0.0,
+        },
+        SpeedLimit(300.0),
+        Bounding::from(3.0),
+        Damage(30.0),
+        BoundaryWrap,
+    ));
+    let across = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(FRAME_START_X + 60.0, 20.0),
+    );
+
+    harness.step(1);
+    assert_eq!(
+        harness.get::<Missile>(missile).unwrap().target,
+        Some(across)
+    );
+
+    let flight = harness.step_until(steps(2.0), |world| world.get_entity(missile).is_none());
+    assert!(flight < steps(2.0), "the missile never got there");
+    assert!(harness.get::<Health>(across).unwrap().0 < AsteroidSize::Large.health().0);
+}
+
+#[test]
+fn missile_blasts_hurt_every_asteroid_in_reach() {
+    let mut harness = Harness::new(
+        SystemStage::single_threaded()
+            .with_system(weapon_control_system)
+            .with_system(drive_system.after(weapon_control_system))
+            .with_system(movement_system.after(drive_system))
+            .with_system(spatial_grid_system.after(movement_system))
+            .with_system(missile_impact_system.after(spatial_grid_system)),
+    );
+    // pointing to the right
+    harness.spawn((
+        Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_2)),
+        Bounding::from(10.0),
+        Loadout::new(vec![GameConfig::default()
+            .weapons
+            .into_iter()
+            .find(|weapon| matches!(weapon.projectile, Projectile::Missile { .. }))
+            .unwrap()]),
+        Heat::new(0.35),
+    ));
+    let struck = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(60.0, 0.0),
+    );
+    let beside = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(60.0, 45.0),
+    );
+    let outside = asteroid(
+        &mut harness,
+        AsteroidSize::Large,
+        ROCK_RADIUS,
+        vec2(60.0, -150.0),
+    );
+
+    press(
+        &mut harness,
+        ControlInput {
+            fire: true,
+            ..default()
+        },
+    );
+    assert_eq!(harness.count::<Missile>(|_| true), 1);
+    harness.step(steps(0.5));
+
+    assert_eq!(
+        harness.count::<Missile>(|_| true),
+        0,
+        "the missile didn't blow up"
+    );
+    let full = AsteroidSize::Large.health().0;
+    assert!(harness.get::<Health>(struck).unwrap().0 < full);
+    assert!(harness.get::<Health>(beside).unwrap().0 < full);
+    assert_eq!(harness.get::<Health>(outside).unwrap().0, full);
+}